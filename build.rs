@@ -0,0 +1,120 @@
+//! Compiles the GLSL shaders in `shaders/` to SPIR-V in `OUT_DIR`, named
+//! after their source with `.spv` appended (e.g. `shader.vert.spv`).
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use glslang::{Compiler, CompilerOptions, Shader, ShaderInput, ShaderSource, ShaderStage};
+
+const SHADER_DIR: &str = "shaders";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SHADER_DIR);
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR is not set"));
+
+    let mut paths = fs::read_dir(SHADER_DIR)
+        .unwrap_or_else(|e| fail(&format!("Failed to read `{}`: {}", SHADER_DIR, e)))
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| fail(&format!("Failed to read `{}`: {}", SHADER_DIR, e)));
+    paths.sort();
+
+    let mut failed = false;
+    for path in paths.iter().filter(|p| get_stage(p).is_some()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+        match compile(path) {
+            Ok(code) => {
+                let name = format!("{}.spv", path.file_name().unwrap().to_string_lossy());
+                let bytes = code.iter().flat_map(|w| w.to_le_bytes()).collect::<Vec<_>>();
+                fs::write(out_dir.join(&name), bytes)
+                    .unwrap_or_else(|e| fail(&format!("Failed to write `{}`: {}", name, e)));
+            }
+            Err(errors) => {
+                errors.iter().for_each(|e| eprintln!("{}", e));
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        fail("Failed to compile shaders.");
+    }
+}
+
+/// Picks the shader stage from the file extension, as `glslc` does.
+fn get_stage(path: &Path) -> Option<ShaderStage> {
+    match path.extension()?.to_str()? {
+        "vert" => Some(ShaderStage::Vertex),
+        "tesc" => Some(ShaderStage::TesselationControl),
+        "tese" => Some(ShaderStage::TesselationEvaluation),
+        "geom" => Some(ShaderStage::Geometry),
+        "frag" => Some(ShaderStage::Fragment),
+        "comp" => Some(ShaderStage::Compute),
+        _ => None,
+    }
+}
+
+/// Compiles the shader at `path`, returning its errors as
+/// `<file>:<line>: error: <message>` on failure.
+fn compile(path: &Path) -> Result<Vec<u32>, Vec<String>> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| vec![format!("{}: error: {}", path.display(), e)])?;
+
+    let compiler = Compiler::acquire().expect("glslang is already in use");
+    let source = ShaderSource::from(source);
+    let options = CompilerOptions::default();
+    let stage = get_stage(path).unwrap();
+
+    let result = ShaderInput::new(&source, stage, &options, None, None)
+        .and_then(|input| Shader::new(compiler, input).parse())
+        .and_then(|shader| shader.compile());
+
+    result.map_err(|e| {
+        let log = match &e {
+            glslang::error::GlslangError::PreprocessError(log)
+            | glslang::error::GlslangError::ParseError(log)
+            | glslang::error::GlslangError::LinkError(log) => log.log.clone(),
+            e => e.to_string(),
+        };
+
+        let errors = log
+            .lines()
+            .filter_map(|l| format_error(path, l))
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            vec![format!("{}: error: {}", path.display(), log.trim())]
+        } else {
+            errors
+        }
+    })
+}
+
+/// Rewrites a glslang message such as `ERROR: 0:12: 'x' : undeclared
+/// identifier` to name the file instead of the source string index.
+fn format_error(path: &Path, line: &str) -> Option<String> {
+    let message = line.strip_prefix("ERROR: ")?.trim_end();
+
+    // Summaries like `1 compilation errors.  No code generated.` add nothing.
+    if message.ends_with("No code generated.") {
+        return None;
+    }
+
+    let located = message.split_once(": ").and_then(|(location, message)| {
+        let (_, line) = location.split_once(':')?;
+        line.parse::<u32>().ok().map(|line| (line, message))
+    });
+
+    Some(match located {
+        Some((line, message)) => format!("{}:{}: error: {}", path.display(), line, message),
+        None => format!("{}: error: {}", path.display(), message),
+    })
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -1,3 +0,0 @@
-C:/VulkanSDK/1.4.304.0/Bin/glslc.exe shaders/shader.vert -o compiled_shaders/vert.spv
-C:/VulkanSDK/1.4.304.0/Bin/glslc.exe shaders/shader.frag -o compiled_shaders/frag.spv
-pause
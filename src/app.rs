@@ -1,169 +1,455 @@
 
 use crate::appdata;
 use crate::appdata::AppData;
+use crate::config::Config;
+use crate::debug::begin_label;
+use crate::debug::end_label;
+use crate::debug::name_objects;
+use crate::depth::create_depth_objects;
 use crate::devices;
+use crate::headless::create_offscreen_target;
+use crate::headless::read_pixels;
+use crate::headless::record_readback;
+use crate::headless::save_png;
 use crate::instance::create_instance;
-use crate::pipeline::create_pipeline;
+use crate::mesh::Mesh;
+use crate::msaa::create_color_objects;
+use crate::msaa::get_msaa_samples;
+use crate::owned::Owned;
+use crate::owned::OwnedDevice;
+use crate::owned::OwnedInstance;
+use crate::mesh::QUAD_INDICES;
+use crate::mesh::QUAD_VERTICES;
+use crate::model::load_model;
+use crate::pipeline::build_pipelines;
+use crate::pipeline::create_pipelines;
 use crate::pipeline::create_render_pass;
+use crate::pipeline::GraphicsPipeline;
+use crate::pipeline::WIREFRAME_PIPELINE;
+use crate::pipeline_cache::create_pipeline_cache;
+use crate::pipeline_cache::save_pipeline_cache;
 use crate::queue_family_indices::QueueFamilyIndices;
+use crate::shaders::reflect_shaders;
+use crate::shaders::ShaderWatcher;
+use crate::shaders::FRAGMENT_SHADER;
+use crate::shaders::VERTEX_SHADER;
 use crate::swapchain::create_swapchain;
 use crate::swapchain::create_swapchain_image_views;
+use crate::texture::create_texture_descriptor_sets;
+use crate::texture::create_texture_set_layout;
+use crate::texture::load_png;
+use crate::texture::Pixels;
+use crate::texture::Texture;
+use crate::uniforms::create_descriptor_pool;
+use crate::uniforms::create_descriptor_set_layout;
+use crate::uniforms::create_descriptor_sets;
+use crate::uniforms::create_uniform_buffers;
+use crate::uniforms::update_uniform_buffer;
+use crate::validation::Validation;
+
+use super::MAX_FRAMES_IN_FLIGHT;
 
-use super::VALIDATION_ENABLED;
-
-use anyhow::Ok;
 use vulkanalia::loader::LibloadingLoader;
 use vulkanalia::vk;
 use vulkanalia::vk::DeviceV1_0;
-use vulkanalia::vk::ExtDebugUtilsExtension;
 use vulkanalia::vk::Handle;
 use vulkanalia::vk::HasBuilder;
-use vulkanalia::vk::InstanceV1_0;
-use vulkanalia::vk::KhrSurfaceExtension;
 use vulkanalia::vk::KhrSwapchainExtension;
-use vulkanalia::window as vk_window;
 use vulkanalia::Device;
 use vulkanalia::Entry;
 use vulkanalia::Instance;
 use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+
 
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
 
 use vulkanalia::loader::LIBRARY;
 use winit::window::Window;
 
-/// Our Vulkan app.
-#[derive(Clone, Debug)]
+/// Our Vulkan app, destroyed when dropped.
+#[derive(Debug)]
 pub struct App {
-    pub entry: Entry,
-    pub instance: Instance,
+    /// Dropped first, destroying everything created from the device.
     pub data: AppData,
-    pub device: Device,
+    // Objects created from the device keep it alive, and it keeps the
+    // instance alive, so both are only destroyed once nothing uses them.
+    // The entry is dropped last, since their commands are loaded from it.
+    pub device: Arc<OwnedDevice>,
+    pub instance: Arc<OwnedInstance>,
+    pub entry: Entry,
+    pub frame: usize,
+    pub resized: bool,
+    /// When the app was created, used to animate the scene.
+    pub start: Instant,
+    /// Watches the shader directory, if shaders are loaded from one.
+    pub shader_watcher: Option<ShaderWatcher>,
 }
 
 impl App 
 {
     /// Creates our Vulkan app.
-    pub unsafe fn create(window: &Window) -> Result<Self> 
+    pub unsafe fn create(window: &Window, config: &Config) -> Result<Self> 
+    {
+        Self::create_for(Some(window), config)
+    }
+
+    /// Creates our Vulkan app rendering into an offscreen image of the
+    /// configured extent instead of a window, so it can run without a display.
+    pub unsafe fn create_headless(config: &Config) -> Result<Self>
+    {
+        Self::create_for(None, config)
+    }
+
+    /// Creates our Vulkan app for `window`, or headless if there is none.
+    unsafe fn create_for(window: Option<&Window>, config: &Config) -> Result<Self>
     {
         let loader = LibloadingLoader::new(LIBRARY)?;
         let entry = Entry::new(loader).map_err(|b| anyhow!("{}", b))?;
-        let mut data = appdata::AppData::default();
-        let instance = create_instance(window, &entry, &mut data)?;
-        data.surface = vk_window::create_surface(&instance, &window, &window)?;
-        devices::pick_physical_device(&instance, &mut data)?;
+        let mut data = appdata::AppData {
+            headless: window.is_none(),
+            validation: Arc::new(Validation::new(config.validation.clone())),
+            shader_dir: config.shaders.clone(),
+            pipeline_cache_path: config.pipeline_cache.clone(),
+            ..Default::default()
+        };
+        let mut instance = create_instance(window, &entry, &mut data)?;
+        if let Some(window) = window {
+            data.surface = instance.create_surface(window)?;
+        }
+        devices::pick_physical_device(&instance, &mut data, config.device.as_deref())?;
+        let instance = Arc::new(instance);
         let device = devices::create_logical_device(&entry, &instance, &mut data)?;
-        create_swapchain(window, &instance, &device, &mut data)?;
-        create_swapchain_image_views(&device, &mut data)?;
-        create_render_pass(&instance, &device, &mut data)?;
-        create_pipeline(&device, &mut data)?;
-        create_framebuffers(&device, &mut data)?;
-        create_command_pool(&instance, &device, &mut data)?;
-        create_command_buffers(&device, &mut data)?;
-        create_sync_objects(&device, &mut data)?;
-        Ok(Self { entry, instance, data, device })
+
+        // If anything below fails, dropping the app destroys what was
+        // created so far, since everything is owned by `data`.
+        let mut app = Self {
+            data,
+            device,
+            instance,
+            entry,
+            frame: 0,
+            resized: false,
+            start: Instant::now(),
+            shader_watcher: None,
+        };
+        app.create_objects(window, config)?;
+        app.start = Instant::now();
+
+        Ok(app)
     }
 
+    /// Creates everything built on top of the logical device.
+    unsafe fn create_objects(&mut self, window: Option<&Window>, config: &Config) -> Result<()>
+    {
+        let (instance, device, data) = (&*self.instance, &self.device, &mut self.data);
+        create_pipeline_cache(instance, device, data, config.pipeline_cache.as_deref())?;
+        data.msaa_samples = get_msaa_samples(instance, data, config.samples);
+        data.sample_shading = config.sample_shading
+            && data.msaa_samples != vk::SampleCountFlags::_1
+            && data.enabled_features.sample_rate_shading == vk::TRUE;
+        match window {
+            Some(window) => create_swapchain(window, instance, device, data)?,
+            None => create_offscreen_target(instance, device, data, config.extent)?,
+        }
+        create_swapchain_image_views(device, data)?;
+        create_render_pass(instance, device, data)?;
+        data.shader_layout = reflect_shaders(data.shader_dir.as_deref())?;
+        create_descriptor_set_layout(device, data)?;
+        create_texture_set_layout(device, data)?;
+        create_pipelines(instance, device, data)?;
+        create_command_pool(instance, device, data)?;
+        create_color_objects(instance, device, data)?;
+        create_depth_objects(instance, device, data)?;
+        create_framebuffers(device, data)?;
+        create_meshes(instance, device, data, config.model.as_deref(), config.wireframe)?;
+        create_textures(instance, device, data, config.texture.as_deref())?;
+        create_texture_descriptor_sets(device, data)?;
+        create_uniform_buffers(instance, device, data)?;
+        create_descriptor_pool(device, data)?;
+        create_descriptor_sets(device, data)?;
+        create_command_buffers(instance, device, data)?;
+        create_sync_objects(device, data)?;
+        name_objects(instance, device, data)?;
+        self.shader_watcher = config.shaders
+            .as_deref()
+            .map(|d| ShaderWatcher::new(d, &[VERTEX_SHADER, FRAGMENT_SHADER]));
+
+        Ok(())
+    }
+
+    /// Renders a frame into the offscreen image of a headless app and saves
+    /// it to `path` as a PNG.
+    pub unsafe fn render_headless(&mut self, path: &Path) -> Result<()> {
+        self.data.validation.check()?;
+        self.reload_shaders()?;
+
+        let in_flight_fence = self.data.in_flight_fences[self.frame];
+
+        self.device.wait_for_fences(&[in_flight_fence], true, u64::MAX)?;
+        self.device.reset_fences(&[in_flight_fence])?;
+
+        update_uniform_buffer(&self.device, &self.data, 0, self.start.elapsed().as_secs_f32())?;
+
+        let command_buffers = &[self.data.command_buffers[0]];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(command_buffers);
+
+        self.device.queue_submit(
+            self.data.graphics_queue, &[submit_info], in_flight_fence)?;
+
+        // The readback buffer is shared by all frames, so each frame has to
+        // be read back before the next one is submitted.
+        self.device.wait_for_fences(&[in_flight_fence], true, u64::MAX)?;
+
+        let pixels = read_pixels(&self.device, &self.data)?;
+        save_png(path, self.data.swapchain_extent, &pixels)?;
+
+        self.frame = (self.frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
+    }
 
     /// Renders a frame for our Vulkan app.
     pub unsafe fn render(&mut self, window: &Window) -> Result<()> {
-        let image_index = self
-            .device
-            .acquire_next_image_khr(
-                self.data.swapchain,
-                u64::MAX,
-                self.data.image_available_semaphore,
-                vk::Fence::null(),
-            )?
-            .0 as usize;
-
-        let wait_semaphores = &[self.data.image_available_semaphore];
+        self.data.validation.check()?;
+        self.reload_shaders()?;
+
+        let in_flight_fence = self.data.in_flight_fences[self.frame];
+
+        self.device.wait_for_fences(&[in_flight_fence], true, u64::MAX)?;
+
+        let result = self.device.acquire_next_image_khr(
+            *self.data.swapchain,
+            u64::MAX,
+            self.data.image_available_semaphores[self.frame],
+            vk::Fence::null(),
+        );
+
+        let (image_index, acquire_suboptimal) = match result {
+            Ok((image_index, code)) => (image_index as usize, code == vk::SuccessCode::SUBOPTIMAL_KHR),
+            Err(vk::ErrorCode::OUT_OF_DATE_KHR) => return self.recreate_swapchain(window),
+            Err(e) => return Err(anyhow!(e)),
+        };
+
+        let image_in_flight = self.data.images_in_flight[image_index];
+        if !image_in_flight.is_null() {
+            self.device.wait_for_fences(&[image_in_flight], true, u64::MAX)?;
+        }
+
+        self.data.images_in_flight[image_index] = in_flight_fence;
+
+        update_uniform_buffer(&self.device, &self.data, image_index, self.start.elapsed().as_secs_f32())?;
+
+        let wait_semaphores = &[self.data.image_available_semaphores[self.frame]];
         let wait_stages = &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let command_buffers = &[self.data.command_buffers[image_index as usize]];
-        let signal_semaphores = &[self.data.render_finished_semaphore];
+        let command_buffers = &[self.data.command_buffers[image_index]];
+        let signal_semaphores = &[self.data.render_finished_semaphores[image_index]];
         let submit_info = vk::SubmitInfo::builder()
             .wait_semaphores(wait_semaphores)
             .wait_dst_stage_mask(wait_stages)
             .command_buffers(command_buffers)
             .signal_semaphores(signal_semaphores);
-            
-        self.device.queue_submit(
-            self.data.graphics_queue, &[submit_info], vk::Fence::null())?;
-            
 
-        let dependency = vk::SubpassDependency::builder()
-            .src_subpass(vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+        self.device.reset_fences(&[in_flight_fence])?;
+
+        self.device.queue_submit(
+            self.data.graphics_queue, &[submit_info], in_flight_fence)?;
 
-        let swapchains = &[self.data.swapchain];
+        let swapchains = &[*self.data.swapchain];
         let image_indices = &[image_index as u32];
         let present_info = vk::PresentInfoKHR::builder()
             .wait_semaphores(signal_semaphores)
             .swapchains(swapchains)
             .image_indices(image_indices);
 
-        self.device.queue_present_khr(self.data.present_queue, &present_info)?;
+        let result = self.device.queue_present_khr(self.data.present_queue, &present_info);
+        let changed = acquire_suboptimal
+            || result == Ok(vk::SuccessCode::SUBOPTIMAL_KHR)
+            || result == Err(vk::ErrorCode::OUT_OF_DATE_KHR);
 
-            
-    Ok(())
-}
+        if self.resized || changed {
+            self.resized = false;
+            self.recreate_swapchain(window)?;
+        } else if let Err(e) = result {
+            return Err(anyhow!(e));
+        }
 
+        self.frame = (self.frame + 1) % MAX_FRAMES_IN_FLIGHT;
 
-    /// Destroys our Vulkan app.
-    pub unsafe fn destroy(&mut self) {
-        if VALIDATION_ENABLED 
-        {
-            self.instance.destroy_debug_utils_messenger_ext(self.data.messenger, None);
+        Ok(())
+    }
+
+
+    /// Rebuilds the pipelines if the shaders in the shader directory changed
+    /// since the last frame. If the new shaders fail to build, the error is
+    /// logged and the old pipelines keep running.
+    unsafe fn reload_shaders(&mut self) -> Result<()> {
+        let Some(watcher) = &mut self.shader_watcher else {
+            return Ok(());
+        };
+
+        let changed = watcher.poll();
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        info!("Reloading pipelines for changed shaders: {}.", changed.join(", "));
+        self.device.device_wait_idle()?;
+
+        match self.rebuild_pipelines() {
+            Ok(pipelines) => {
+                // Replacing the old pipelines destroys them.
+                self.data.pipelines = pipelines;
+
+                // The command buffers reference the old pipelines.
+                self.device.free_command_buffers(*self.data.command_pool, &self.data.command_buffers);
+                create_command_buffers(&self.instance, &self.device, &mut self.data)?;
+                name_objects(&self.instance, &self.device, &self.data)?;
+            }
+            Err(e) => error!("Failed to reload shaders, keeping the old pipelines: {:#}", e),
         }
 
-        self.data.framebuffers
-            .iter()
-            .for_each(|f| self.device.destroy_framebuffer(*f, None));
-
-        self.data.swapchain_image_views
-            .iter()
-            .for_each(|v| self.device.destroy_image_view(*v, None));
-
-        self.device.destroy_semaphore(self.data.render_finished_semaphore, None);
-        self.device.destroy_semaphore(self.data.image_available_semaphore, None);
-        self.device.destroy_swapchain_khr(self.data.swapchain, None);
-        self.instance.destroy_surface_khr(self.data.surface, None);
-        self.device.destroy_render_pass(self.data.render_pass, None);
-        self.device.destroy_pipeline_layout(self.data.pipeline_layout, None);
-        self.device.destroy_pipeline(self.data.pipeline, None);
-        self.device.destroy_command_pool(self.data.command_pool, None);
-        self.device.destroy_device(None);
-        self.instance.destroy_instance(None);
+        Ok(())
+    }
+
+    /// Builds new pipelines from the shaders currently in the shader
+    /// directory, without replacing the current ones.
+    unsafe fn rebuild_pipelines(&self) -> Result<Owned<Vec<GraphicsPipeline>>> {
+        // Our descriptor set layouts, push constants and vertex buffers
+        // outlive the pipelines, so the shaders have to keep the interface
+        // they were created for, even if they changed on disk since.
+        let layout = reflect_shaders(self.data.shader_dir.as_deref())?;
+        layout.check_unchanged(&self.data.shader_layout)?;
+        build_pipelines(&self.instance, &self.device, &self.data)
+    }
+
+    /// Recreates the swapchain and everything that depends on its images
+    /// or extent, e.g. after the window was resized.
+    pub unsafe fn recreate_swapchain(&mut self, window: &Window) -> Result<()> {
+        // A zero-sized swapchain is invalid; try again once the window has an area.
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            self.resized = true;
+            return Ok(());
+        }
+
+        self.device.device_wait_idle()?;
+
+        let old_format = self.data.swapchain_format;
+        self.destroy_swapchain_resources();
+        // The old swapchain is retired by the new one and destroyed once it
+        // is replaced.
+        create_swapchain(window, &self.instance, &self.device, &mut self.data)?;
+
+        // The pipelines use a dynamic viewport and scissor, so they only
+        // depend on the render pass, which only depends on the format.
+        // Replacing either destroys the old one.
+        if self.data.swapchain_format != old_format {
+            create_render_pass(&self.instance, &self.device, &mut self.data)?;
+            self.data.pipelines = self.rebuild_pipelines()?;
+        }
+
+        create_swapchain_image_views(&self.device, &mut self.data)?;
+        create_color_objects(&self.instance, &self.device, &mut self.data)?;
+        create_depth_objects(&self.instance, &self.device, &mut self.data)?;
+        create_framebuffers(&self.device, &mut self.data)?;
+        create_uniform_buffers(&self.instance, &self.device, &mut self.data)?;
+        create_descriptor_pool(&self.device, &mut self.data)?;
+        create_descriptor_sets(&self.device, &mut self.data)?;
+        create_command_buffers(&self.instance, &self.device, &mut self.data)?;
+        create_swapchain_sync_objects(&self.device, &mut self.data)?;
+        name_objects(&self.instance, &self.device, &self.data)?;
+
+        Ok(())
+    }
+
+    /// Destroys our Vulkan app, failing afterwards if validation reported
+    /// errors and fail-fast mode is on.
+    pub fn destroy(self) -> Result<()> {
+        let validation = self.data.validation.clone();
+        drop(self);
+        validation.check()
+    }
+
+    /// Destroys everything built on top of the current swapchain, in reverse
+    /// order of creation, leaving the swapchain itself alive so it can be
+    /// passed as `old_swapchain` when recreating.
+    unsafe fn destroy_swapchain_resources(&mut self) {
+        let data = &mut self.data;
+        data.render_finished_semaphores = Default::default();
+        if !data.command_buffers.is_empty() {
+            self.device.free_command_buffers(*data.command_pool, &data.command_buffers);
+            data.command_buffers.clear();
+        }
+        data.descriptor_pool = Default::default();
+        data.descriptor_sets.clear();
+        data.uniform_buffers = Default::default();
+        data.uniform_buffers_memory = Default::default();
+        data.framebuffers = Default::default();
+        data.depth_image_view = Default::default();
+        data.depth_image = Default::default();
+        data.depth_image_memory = Default::default();
+        data.color_image_view = Default::default();
+        data.color_image = Default::default();
+        data.color_image_memory = Default::default();
+        data.swapchain_image_views = Default::default();
     }
 }
 
-unsafe fn create_framebuffers(device: &Device, data: &mut AppData) -> Result<()> {
-    data.framebuffers = data
-        .swapchain_image_views
-        .iter()
-        .map(|i| {
+impl Drop for App {
+    fn drop(&mut self) {
+        unsafe {
+            // Nothing may be destroyed while the device could still use it.
+            if let Err(e) = self.device.device_wait_idle() {
+                warn!("Failed to wait for the device to be idle: {}", e);
+            }
+
+            let cache_path = self.data.pipeline_cache_path
+                .as_ref()
+                .filter(|_| !self.data.pipeline_cache.is_null());
+            if let Some(path) = cache_path {
+                if let Err(e) = save_pipeline_cache(&self.device, &self.data, path) {
+                    warn!("Failed to save pipeline cache `{}`: {}", path.display(), e);
+                }
+            }
+        }
+
+        // Dropping the fields destroys everything else.
+    }
+}
 
-            let attachments = &[*i];
-            let create_info = vk::FramebufferCreateInfo::builder()
-                .render_pass(data.render_pass)
-                .attachments(attachments)
-                .width(data.swapchain_extent.width)
-                .height(data.swapchain_extent.height)
-                .layers(1);
+unsafe fn create_framebuffers(device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
+    // Pushed as they are created, so a failure destroys the earlier ones.
+    let mut framebuffers = Owned::new(device, vec![]);
+    for i in data.swapchain_image_views.iter() {
+        let attachments: &[_] = if data.msaa_samples == vk::SampleCountFlags::_1 {
+            &[*i, *data.depth_image_view]
+        } else {
+            &[*data.color_image_view, *data.depth_image_view, *i]
+        };
+        let create_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(*data.render_pass)
+            .attachments(attachments)
+            .width(data.swapchain_extent.width)
+            .height(data.swapchain_extent.height)
+            .layers(1);
+
+        framebuffers.push(device.create_framebuffer(&create_info, None)?);
+    }
 
-            device.create_framebuffer(&create_info, None)
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+    data.framebuffers = framebuffers;
 
     Ok(())
 }
 
 unsafe fn create_command_pool(
     instance: &Instance,
-    device: &Device,
+    device: &Arc<OwnedDevice>,
     data: &mut AppData,
 ) -> Result<()> {
 
@@ -173,15 +459,112 @@ unsafe fn create_command_pool(
         .flags(vk::CommandPoolCreateFlags::empty()) // Optional.
         .queue_family_index(indices.graphics);
 
-    data.command_pool = device.create_command_pool(&info, None)?;
+    data.command_pool = Owned::new(device, device.create_command_pool(&info, None)?);
 
     Ok(())
 }
 
-unsafe fn create_command_buffers(device: &Device, data: &mut AppData) -> Result<()> {
+/// Uploads the meshes of `model`, or the built-in quad if there is none,
+/// drawn as wireframes if `wireframe` is set.
+unsafe fn create_meshes(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &mut AppData,
+    model: Option<&Path>,
+    wireframe: bool,
+) -> Result<()> {
+    let pipeline = if wireframe {
+        if data.pipelines.len() <= WIREFRAME_PIPELINE {
+            return Err(anyhow!("Wireframes are not supported by the device."));
+        }
+        WIREFRAME_PIPELINE
+    } else {
+        0
+    };
+
+    data.meshes = Owned::new(device, vec![]);
+
+    let Some(path) = model else {
+        let mut mesh = Mesh::create(instance, device, data, &QUAD_VERTICES, &QUAD_INDICES)?;
+        mesh.pipeline = pipeline;
+        data.meshes.push(mesh);
+        return Ok(());
+    };
+
+    let model = load_model(path)?;
+    for mesh_data in &model.meshes {
+        let mut mesh = Mesh::create(instance, device, data, &mesh_data.vertices, &mesh_data.indices)?;
+        mesh.material = mesh_data.material;
+        mesh.pipeline = pipeline;
+        data.meshes.push(mesh);
+
+        info!(
+            "Uploaded mesh `{}` ({} vertices, {} indices, material `{}`).",
+            mesh_data.name,
+            mesh_data.vertices.len(),
+            mesh_data.indices.len(),
+            mesh_data.material.map(|m| model.materials[m].name.as_str()).unwrap_or("none"),
+        );
+    }
+
+    data.materials = model.materials;
+
+    Ok(())
+}
+
+/// Loads the diffuse textures of the meshes' materials, falling back to
+/// `fallback` or plain white for meshes without one.
+unsafe fn create_textures(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &mut AppData,
+    fallback: Option<&Path>,
+) -> Result<()> {
+    let white = Pixels { width: 1, height: 1, rgba: vec![255; 4] };
+    let white = Texture::create(instance, device, data, &white)?;
+    data.textures = Owned::new(device, vec![white]);
+
+    let mut loaded = HashMap::<PathBuf, usize>::new();
+    let mut load = |data: &mut AppData, path: &Path| -> Result<usize> {
+        if let Some(index) = loaded.get(path) {
+            return Ok(*index);
+        }
+
+        let texture = Texture::create(instance, device, data, &load_png(path)?)?;
+        data.textures.push(texture);
+        loaded.insert(path.to_path_buf(), data.textures.len() - 1);
+        info!("Loaded texture `{}`.", path.display());
+
+        Ok(data.textures.len() - 1)
+    };
+
+    let fallback = match fallback {
+        Some(path) => load(data, path)?,
+        None => 0,
+    };
+
+    for i in 0..data.meshes.len() {
+        let path = data.meshes[i]
+            .material
+            .and_then(|m| data.materials[m].diffuse_texture.clone());
+
+        data.meshes[i].texture = match path {
+            Some(path) => load(data, &path)?,
+            None => fallback,
+        };
+    }
+
+    Ok(())
+}
+
+unsafe fn create_command_buffers(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+) -> Result<()> {
     
     let allocate_info = vk::CommandBufferAllocateInfo::builder()
-        .command_pool(data.command_pool)
+        .command_pool(*data.command_pool)
         .level(vk::CommandBufferLevel::PRIMARY)
         .command_buffer_count(data.framebuffers.len() as u32);
 
@@ -206,33 +589,125 @@ unsafe fn create_command_buffers(device: &Device, data: &mut AppData) -> Result<
             },
         };
             
-        let clear_values = &[color_clear_value];
+        let depth_clear_value = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+        };
+
+        let clear_values = &[color_clear_value, depth_clear_value];
         let info = vk::RenderPassBeginInfo::builder()
-            .render_pass(data.render_pass)
+            .render_pass(*data.render_pass)
             .framebuffer(data.framebuffers[i])
             .render_area(render_area)
             .clear_values(clear_values);
 
+        begin_label(instance, data, *command_buffer, "Render pass")?;
         device.cmd_begin_render_pass(
             *command_buffer, &info, vk::SubpassContents::INLINE);
+
+        // Our pipelines leave the viewport and scissor dynamic, so the same
+        // pipeline can draw into any extent or region of it.
+        let viewport = vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(data.swapchain_extent.width as f32)
+            .height(data.swapchain_extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+
+        device.cmd_set_viewport(*command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(*command_buffer, 0, &[render_area]);
             
-        device.cmd_bind_pipeline(
-            *command_buffer, vk::PipelineBindPoint::GRAPHICS, data.pipeline);
+        // Meshes sharing a pipeline are usually adjacent, so only rebind
+        // when it changes.
+        let mut bound = None;
+        for (m, mesh) in data.meshes.iter().enumerate() {
+            begin_label(instance, data, *command_buffer, &format!("Draw mesh {}", m))?;
+
+            let pipeline = data.pipelines[mesh.pipeline];
+            if bound != Some(mesh.pipeline) {
+                device.cmd_bind_pipeline(
+                    *command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.pipeline);
+
+                device.cmd_bind_descriptor_sets(
+                    *command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.layout,
+                    0,
+                    &[data.descriptor_sets[i]],
+                    &[],
+                );
+
+                bound = Some(mesh.pipeline);
+            }
+
+            device.cmd_bind_descriptor_sets(
+                *command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.layout,
+                1,
+                &[data.textures[mesh.texture].descriptor_set],
+                &[],
+            );
+
+            mesh.record_draw(device, *command_buffer, pipeline.layout);
+            end_label(instance, data, *command_buffer);
+        }
 
-        device.cmd_draw(*command_buffer, 3, 1, 0, 0);
         device.cmd_end_render_pass(*command_buffer);
+        end_label(instance, data, *command_buffer);
+
+        if data.headless {
+            begin_label(instance, data, *command_buffer, "Readback")?;
+            record_readback(device, data, *command_buffer);
+            end_label(instance, data, *command_buffer);
+        }
+
         device.end_command_buffer(*command_buffer)?;
     }
     
     Ok(())
 }
 
-unsafe fn create_sync_objects(device: &Device, data: &mut AppData) -> Result<()> {
+unsafe fn create_sync_objects(device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
+    let semaphore_info = vk::SemaphoreCreateInfo::builder();
+    let fence_info = vk::FenceCreateInfo::builder()
+        .flags(vk::FenceCreateFlags::SIGNALED);
+
+    data.image_available_semaphores = Owned::new(device, vec![]);
+    data.in_flight_fences = Owned::new(device, vec![]);
+
+    for _ in 0..MAX_FRAMES_IN_FLIGHT {
+        data.image_available_semaphores.push(device.create_semaphore(&semaphore_info, None)?);
+        data.in_flight_fences.push(device.create_fence(&fence_info, None)?);
+    }
+
+    create_swapchain_sync_objects(device, data)?;
+
+    Ok(())
+}
+
+/// Creates the synchronization objects whose count follows the number of
+/// swapchain images.
+unsafe fn create_swapchain_sync_objects(device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
     let semaphore_info = vk::SemaphoreCreateInfo::builder();
 
-    data.image_available_semaphore = device.create_semaphore(&semaphore_info, None)?;
-    data.render_finished_semaphore = device.create_semaphore(&semaphore_info, None)?;
+    // Presentation may still be reading a render-finished semaphore when the
+    // next frame starts, so these are tied to swapchain images, not frames.
+    let mut render_finished_semaphores = Owned::new(device, vec![]);
+    for _ in &data.swapchain_images {
+        render_finished_semaphores.push(device.create_semaphore(&semaphore_info, None)?);
+    }
+
+    data.render_finished_semaphores = render_finished_semaphores;
 
+    data.images_in_flight = data
+        .swapchain_images
+        .iter()
+        .map(|_| vk::Fence::null())
+        .collect();
 
     Ok(())
 }
\ No newline at end of file
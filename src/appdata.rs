@@ -1,26 +1,83 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use vulkanalia::vk;
 
+use crate::mesh::Mesh;
+use crate::model::Material;
+use crate::owned::Owned;
+use crate::pipeline::GraphicsPipeline;
+use crate::reflect::ShaderLayout;
+use crate::texture::Texture;
+use crate::validation::Validation;
+
 /// The Vulkan handles and associated properties used by our Vulkan app.
-#[derive(Clone, Debug, Default)]
+///
+/// The objects created from the device are owned here and destroyed when
+/// dropped. Fields are dropped in declaration order, so they are declared in
+/// the reverse of the order they are created in.
+#[derive(Debug, Default)]
 pub struct AppData {
-    pub messenger: vk::DebugUtilsMessengerEXT,
+    /// Whether we render into an offscreen image instead of a surface.
+    pub headless: bool,
+    /// Shared with the debug callback, which gets a pointer to it.
+    pub validation: Arc<Validation>,
     pub physical_device: vk::PhysicalDevice,
+    /// The optional device features enabled on the logical device.
+    pub enabled_features: vk::PhysicalDeviceFeatures,
+    pub msaa_samples: vk::SampleCountFlags,
+    /// Whether fragments are shaded per sample rather than per pixel.
+    pub sample_shading: bool,
     pub graphics_queue: vk::Queue,
     pub surface: vk::SurfaceKHR,
     pub present_queue: vk::Queue,
     pub swapchain_format: vk::Format,
     pub swapchain_extent: vk::Extent2D,
-    pub swapchain: vk::SwapchainKHR,
+    /// The swapchain images, or the single offscreen image in headless mode.
     pub swapchain_images: Vec<vk::Image>,
-    pub swapchain_image_views: Vec<vk::ImageView>,
-    pub render_pass: vk::RenderPass,
-    pub pipeline_layout: vk::PipelineLayout,
-    pub pipeline: vk::Pipeline,
-    pub framebuffers: Vec<vk::Framebuffer>,
-    pub command_pool: vk::CommandPool,
+    /// The directory shaders are loaded from instead of the built-in ones.
+    pub shader_dir: Option<PathBuf>,
+    /// The interface of our shaders, which our descriptor set layouts were
+    /// created from.
+    pub shader_layout: ShaderLayout,
+    /// The file the pipeline cache is loaded from and saved to.
+    pub pipeline_cache_path: Option<PathBuf>,
+    pub materials: Vec<Material>,
+    /// Freed along with `descriptor_pool`.
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    /// Freed along with `command_pool`.
     pub command_buffers: Vec<vk::CommandBuffer>,
-    pub image_available_semaphore: vk::Semaphore,
-    pub render_finished_semaphore: vk::Semaphore,
-    pub in_flight_fences: Vec<vk::Fence>,
     pub images_in_flight: Vec<vk::Fence>,
-}
\ No newline at end of file
+    pub in_flight_fences: Owned<Vec<vk::Fence>>,
+    pub render_finished_semaphores: Owned<Vec<vk::Semaphore>>,
+    pub image_available_semaphores: Owned<Vec<vk::Semaphore>>,
+    pub descriptor_pool: Owned<vk::DescriptorPool>,
+    pub uniform_buffers: Owned<Vec<vk::Buffer>>,
+    pub uniform_buffers_memory: Owned<Vec<vk::DeviceMemory>>,
+    pub texture_descriptor_pool: Owned<vk::DescriptorPool>,
+    /// The loaded textures; the first one is plain white.
+    pub textures: Owned<Vec<Texture>>,
+    pub meshes: Owned<Vec<Mesh>>,
+    pub framebuffers: Owned<Vec<vk::Framebuffer>>,
+    pub depth_image_view: Owned<vk::ImageView>,
+    pub depth_image: Owned<vk::Image>,
+    pub depth_image_memory: Owned<vk::DeviceMemory>,
+    pub color_image_view: Owned<vk::ImageView>,
+    pub color_image: Owned<vk::Image>,
+    pub color_image_memory: Owned<vk::DeviceMemory>,
+    pub command_pool: Owned<vk::CommandPool>,
+    /// The pipelines meshes can be drawn with, see `Mesh::pipeline`.
+    pub pipelines: Owned<Vec<GraphicsPipeline>>,
+    pub texture_set_layout: Owned<vk::DescriptorSetLayout>,
+    pub descriptor_set_layout: Owned<vk::DescriptorSetLayout>,
+    pub render_pass: Owned<vk::RenderPass>,
+    pub swapchain_image_views: Owned<Vec<vk::ImageView>>,
+    pub readback_buffer: Owned<vk::Buffer>,
+    pub readback_buffer_memory: Owned<vk::DeviceMemory>,
+    /// The image in `swapchain_images` in headless mode. Swapchain images
+    /// belong to the swapchain instead.
+    pub offscreen_image: Owned<vk::Image>,
+    pub offscreen_image_memory: Owned<vk::DeviceMemory>,
+    pub swapchain: Owned<vk::SwapchainKHR>,
+    pub pipeline_cache: Owned<vk::PipelineCache>,
+}
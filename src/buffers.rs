@@ -0,0 +1,97 @@
+use std::mem::size_of_val;
+use std::ptr::copy_nonoverlapping as memcpy;
+use std::sync::Arc;
+
+use anyhow::Result;
+use vulkanalia::vk::{self, DeviceV1_0, HasBuilder};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+use crate::commands::{begin_single_time_commands, end_single_time_commands};
+use crate::memory::get_memory_type_index;
+use crate::owned::{Owned, OwnedDevice};
+
+/// Creates a buffer of `size` bytes backed by its own memory allocation.
+pub unsafe fn create_buffer(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &AppData,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    properties: vk::MemoryPropertyFlags,
+) -> Result<(Owned<vk::Buffer>, Owned<vk::DeviceMemory>)> {
+    let buffer_info = vk::BufferCreateInfo::builder()
+        .size(size)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+    let buffer = Owned::new(device, device.create_buffer(&buffer_info, None)?);
+
+    let requirements = device.get_buffer_memory_requirements(*buffer);
+    let memory_info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(requirements.size)
+        .memory_type_index(get_memory_type_index(instance, data, properties, requirements)?);
+
+    let buffer_memory = Owned::new(device, device.allocate_memory(&memory_info, None)?);
+    device.bind_buffer_memory(*buffer, *buffer_memory, 0)?;
+
+    Ok((buffer, buffer_memory))
+}
+
+/// Copies `size` bytes between two buffers and waits for the copy to finish.
+pub unsafe fn copy_buffer(
+    device: &Device,
+    data: &AppData,
+    source: vk::Buffer,
+    destination: vk::Buffer,
+    size: vk::DeviceSize,
+) -> Result<()> {
+    let command_buffer = begin_single_time_commands(device, data)?;
+
+    let regions = vk::BufferCopy::builder().size(size);
+    device.cmd_copy_buffer(command_buffer, source, destination, &[regions]);
+
+    end_single_time_commands(device, data, command_buffer)?;
+
+    Ok(())
+}
+
+/// Creates a device-local buffer holding `items`, uploaded through a
+/// temporary host-visible staging buffer.
+pub unsafe fn create_device_local_buffer<T: Copy>(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &AppData,
+    items: &[T],
+    usage: vk::BufferUsageFlags,
+) -> Result<(Owned<vk::Buffer>, Owned<vk::DeviceMemory>)> {
+    let size = size_of_val(items) as vk::DeviceSize;
+
+    let (staging_buffer, staging_buffer_memory) = create_buffer(
+        instance,
+        device,
+        data,
+        size,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+    )?;
+
+    let memory = device.map_memory(*staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())?;
+    memcpy(items.as_ptr(), memory.cast(), items.len());
+    device.unmap_memory(*staging_buffer_memory);
+
+    let (buffer, buffer_memory) = create_buffer(
+        instance,
+        device,
+        data,
+        size,
+        vk::BufferUsageFlags::TRANSFER_DST | usage,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    // The staging buffer is destroyed when dropped on return, and so is the
+    // new buffer if the copy fails.
+    copy_buffer(device, data, *staging_buffer, *buffer, size)?;
+
+    Ok((buffer, buffer_memory))
+}
@@ -0,0 +1,155 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::vk;
+
+use crate::validation::{parse_fail_fast, parse_severity, parse_suppressed, parse_types, ValidationConfig};
+
+/// The environment variable selecting the physical device, like `--device`.
+pub const DEVICE_VAR: &str = "VULKAN_DEVICE";
+
+/// Runtime options for our Vulkan app, parsed from the command line.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Render into an offscreen image instead of a window.
+    pub headless: bool,
+    /// The number of frames to render in headless mode.
+    pub frames: u32,
+    /// The directory headless frames are written to.
+    pub output: PathBuf,
+    /// The size of the window or offscreen image.
+    pub extent: vk::Extent2D,
+    /// The OBJ model to display instead of the built-in quad.
+    pub model: Option<PathBuf>,
+    /// The PNG texture applied to meshes whose material has none.
+    pub texture: Option<PathBuf>,
+    /// The requested MSAA sample count, lowered to what the device supports.
+    pub samples: vk::SampleCountFlags,
+    /// Shade every sample instead of every pixel when multisampling.
+    pub sample_shading: bool,
+    /// Draw the meshes as wireframes.
+    pub wireframe: bool,
+    /// The directory to load compiled shaders from, reloading them when
+    /// they change, instead of using the built-in ones. It has to hold
+    /// `shader.vert.spv` and `shader.frag.spv` compiled from `shaders/`,
+    /// see the README.
+    pub shaders: Option<PathBuf>,
+    /// The file the pipeline cache is loaded from and saved to, by default
+    /// `pipeline_cache.bin` next to the executable. Without one, pipelines
+    /// are compiled from scratch on every run and nothing is written to disk.
+    pub pipeline_cache: Option<PathBuf>,
+    /// The physical device to use instead of the best scoring one, given as
+    /// an index or a name substring.
+    pub device: Option<String>,
+    /// Print a report on every physical device instead of running.
+    pub list_devices: bool,
+    /// Print the device report as JSON instead of text.
+    pub json: bool,
+    pub validation: ValidationConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            headless: false,
+            frames: 1,
+            output: PathBuf::from("output"),
+            extent: vk::Extent2D { width: 1024, height: 768 },
+            model: None,
+            texture: None,
+            samples: vk::SampleCountFlags::_4,
+            sample_shading: false,
+            wireframe: false,
+            shaders: None,
+            pipeline_cache: default_pipeline_cache(),
+            device: None,
+            list_devices: false,
+            json: false,
+            validation: ValidationConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Parses the arguments our process was started with, falling back to
+    /// environment variables for options not given as arguments.
+    pub fn from_args() -> Result<Self> {
+        let mut config = Self {
+            device: std::env::var(DEVICE_VAR).ok().filter(|d| !d.is_empty()),
+            ..Self::default()
+        };
+        config.validation.apply_env()?;
+        config.apply_args(std::env::args().skip(1))?;
+        Ok(config)
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut config = Self::default();
+        config.apply_args(args)?;
+        Ok(config)
+    }
+
+    fn apply_args(&mut self, args: impl IntoIterator<Item = String>) -> Result<()> {
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("Missing value for `{}`.", arg));
+            match arg.as_str() {
+                "--headless" => self.headless = true,
+                "--frames" => self.frames = value()?.parse()?,
+                "--output" => self.output = PathBuf::from(value()?),
+                "--size" => self.extent = parse_extent(&value()?)?,
+                "--model" => self.model = Some(PathBuf::from(value()?)),
+                "--texture" => self.texture = Some(PathBuf::from(value()?)),
+                "--samples" => self.samples = parse_samples(&value()?)?,
+                "--sample-shading" => self.sample_shading = true,
+                "--wireframe" => self.wireframe = true,
+                "--shaders" => self.shaders = Some(PathBuf::from(value()?)),
+                "--pipeline-cache" => self.pipeline_cache = Some(PathBuf::from(value()?)),
+                "--no-pipeline-cache" => self.pipeline_cache = None,
+                "--device" => self.device = Some(value()?),
+                "--list-devices" => self.list_devices = true,
+                "--json" => self.json = true,
+                "--validation" => self.validation.enabled = true,
+                "--no-validation" => self.validation.enabled = false,
+                "--validation-severity" => self.validation.severity = parse_severity(&value()?)?,
+                "--validation-types" => self.validation.types = parse_types(&value()?)?,
+                "--suppress" => self.validation.suppressed.extend(parse_suppressed(&value()?)),
+                "--fail-fast" => self.validation.fail_fast = parse_fail_fast(&value()?)?,
+                _ => return Err(anyhow!("Unknown argument `{}`.", arg)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The pipeline cache next to our executable, if its location is known.
+fn default_pipeline_cache() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join("pipeline_cache.bin"))
+}
+
+/// Parses a sample count such as `4` into its flag.
+fn parse_samples(value: &str) -> Result<vk::SampleCountFlags> {
+    let count = value.parse::<u32>()?;
+    if !count.is_power_of_two() || count > 64 {
+        return Err(anyhow!("Invalid sample count `{}`.", value));
+    }
+
+    Ok(vk::SampleCountFlags::from_bits_truncate(count))
+}
+
+/// Parses an extent written as `<width>x<height>`.
+fn parse_extent(value: &str) -> Result<vk::Extent2D> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| anyhow!("Expected `<width>x<height>`, got `{}`.", value))?;
+
+    let extent = vk::Extent2D { width: width.parse()?, height: height.parse()? };
+    if extent.width == 0 || extent.height == 0 {
+        return Err(anyhow!("Extent `{}` has no area.", value));
+    }
+
+    Ok(extent)
+}
@@ -0,0 +1,164 @@
+use std::ffi::CString;
+
+use anyhow::Result;
+use vulkanalia::vk::{self, DeviceV1_0, ExtDebugUtilsExtension, Handle, HasBuilder};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+
+/// Whether `VK_EXT_debug_utils` is enabled, which it is along with the
+/// validation layer. Everything in this module does nothing without it.
+fn is_enabled(data: &AppData) -> bool {
+    data.validation.config.enabled
+}
+
+/// Names `handle` so validation messages and debuggers show `name` instead
+/// of its raw value.
+pub unsafe fn set_object_name<H>(
+    instance: &Instance,
+    device: &Device,
+    data: &AppData,
+    handle: H,
+    name: &str,
+) -> Result<()>
+where
+    H: Handle,
+    u64: TryFrom<H::Repr>,
+{
+    if !is_enabled(data) || handle.is_null() {
+        return Ok(());
+    }
+
+    let Ok(raw) = u64::try_from(handle.as_raw()) else {
+        return Ok(());
+    };
+
+    let name = CString::new(name)?;
+    let info = vk::DebugUtilsObjectNameInfoEXT::builder()
+        .object_type(H::TYPE)
+        .object_handle(raw)
+        .object_name(name.as_bytes_with_nul());
+
+    instance.set_debug_utils_object_name_ext(device.handle(), &info)?;
+
+    Ok(())
+}
+
+/// Names each of `handles` `<name> <index>`.
+unsafe fn set_object_names<H>(
+    instance: &Instance,
+    device: &Device,
+    data: &AppData,
+    handles: &[H],
+    name: &str,
+) -> Result<()>
+where
+    H: Handle,
+    u64: TryFrom<H::Repr>,
+{
+    for (i, handle) in handles.iter().enumerate() {
+        set_object_name(instance, device, data, *handle, &format!("{} {}", name, i))?;
+    }
+
+    Ok(())
+}
+
+/// Names every object in `data`. Called again whenever objects are
+/// recreated, since the new handles start out unnamed.
+pub unsafe fn name_objects(instance: &Instance, device: &Device, data: &AppData) -> Result<()> {
+    if !is_enabled(data) {
+        return Ok(());
+    }
+
+    set_object_name(instance, device, data, device.handle(), "Device")?;
+    set_object_name(instance, device, data, data.graphics_queue, "Graphics queue")?;
+    if data.present_queue != data.graphics_queue {
+        set_object_name(instance, device, data, data.present_queue, "Present queue")?;
+    }
+    set_object_name(instance, device, data, data.surface, "Surface")?;
+
+    set_object_name(instance, device, data, *data.swapchain, "Swapchain")?;
+    if data.headless {
+        set_object_names(instance, device, data, &data.swapchain_images, "Offscreen image")?;
+        set_object_name(instance, device, data, *data.offscreen_image_memory, "Offscreen image memory")?;
+        set_object_name(instance, device, data, *data.readback_buffer, "Readback buffer")?;
+        set_object_name(instance, device, data, *data.readback_buffer_memory, "Readback buffer memory")?;
+    } else {
+        set_object_names(instance, device, data, &data.swapchain_images, "Swapchain image")?;
+    }
+    set_object_names(instance, device, data, &data.swapchain_image_views, "Swapchain image view")?;
+
+    set_object_name(instance, device, data, *data.color_image, "Color image")?;
+    set_object_name(instance, device, data, *data.color_image_memory, "Color image memory")?;
+    set_object_name(instance, device, data, *data.color_image_view, "Color image view")?;
+    set_object_name(instance, device, data, *data.depth_image, "Depth image")?;
+    set_object_name(instance, device, data, *data.depth_image_memory, "Depth image memory")?;
+    set_object_name(instance, device, data, *data.depth_image_view, "Depth image view")?;
+
+    set_object_name(instance, device, data, *data.render_pass, "Render pass")?;
+    set_object_names(instance, device, data, &data.framebuffers, "Framebuffer")?;
+    set_object_name(instance, device, data, *data.descriptor_set_layout, "Uniform set layout")?;
+    set_object_name(instance, device, data, *data.texture_set_layout, "Texture set layout")?;
+    set_object_name(instance, device, data, *data.pipeline_cache, "Pipeline cache")?;
+    for (i, pipeline) in data.pipelines.iter().enumerate() {
+        set_object_name(instance, device, data, pipeline.pipeline, &format!("Pipeline {}", i))?;
+        set_object_name(instance, device, data, pipeline.layout, &format!("Pipeline layout {}", i))?;
+    }
+
+    for (i, mesh) in data.meshes.iter().enumerate() {
+        set_object_name(instance, device, data, mesh.vertex_buffer, &format!("Mesh {} vertex buffer", i))?;
+        set_object_name(instance, device, data, mesh.vertex_buffer_memory, &format!("Mesh {} vertex buffer memory", i))?;
+        set_object_name(instance, device, data, mesh.index_buffer, &format!("Mesh {} index buffer", i))?;
+        set_object_name(instance, device, data, mesh.index_buffer_memory, &format!("Mesh {} index buffer memory", i))?;
+    }
+
+    set_object_name(instance, device, data, *data.texture_descriptor_pool, "Texture descriptor pool")?;
+    for (i, texture) in data.textures.iter().enumerate() {
+        set_object_name(instance, device, data, texture.image, &format!("Texture {} image", i))?;
+        set_object_name(instance, device, data, texture.image_memory, &format!("Texture {} image memory", i))?;
+        set_object_name(instance, device, data, texture.image_view, &format!("Texture {} image view", i))?;
+        set_object_name(instance, device, data, texture.sampler, &format!("Texture {} sampler", i))?;
+        set_object_name(instance, device, data, texture.descriptor_set, &format!("Texture {} descriptor set", i))?;
+    }
+
+    set_object_names(instance, device, data, &data.uniform_buffers, "Uniform buffer")?;
+    set_object_names(instance, device, data, &data.uniform_buffers_memory, "Uniform buffer memory")?;
+    set_object_name(instance, device, data, *data.descriptor_pool, "Uniform descriptor pool")?;
+    set_object_names(instance, device, data, &data.descriptor_sets, "Uniform descriptor set")?;
+
+    set_object_name(instance, device, data, *data.command_pool, "Command pool")?;
+    set_object_names(instance, device, data, &data.command_buffers, "Command buffer")?;
+    set_object_names(instance, device, data, &data.image_available_semaphores, "Image available semaphore")?;
+    set_object_names(instance, device, data, &data.render_finished_semaphores, "Render finished semaphore")?;
+    set_object_names(instance, device, data, &data.in_flight_fences, "In flight fence")?;
+
+    Ok(())
+}
+
+/// Opens a region named `name` in `command_buffer`, which debuggers group
+/// the commands recorded until the matching `end_label` under.
+pub unsafe fn begin_label(
+    instance: &Instance,
+    data: &AppData,
+    command_buffer: vk::CommandBuffer,
+    name: &str,
+) -> Result<()> {
+    if !is_enabled(data) {
+        return Ok(());
+    }
+
+    let name = CString::new(name)?;
+    let info = vk::DebugUtilsLabelEXT::builder()
+        .label_name(name.as_bytes_with_nul());
+
+    instance.cmd_begin_debug_utils_label_ext(command_buffer, &info);
+
+    Ok(())
+}
+
+/// Closes the region opened last by `begin_label` in `command_buffer`.
+pub unsafe fn end_label(instance: &Instance, data: &AppData, command_buffer: vk::CommandBuffer) {
+    if is_enabled(data) {
+        instance.cmd_end_debug_utils_label_ext(command_buffer);
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::vk::{self, InstanceV1_0};
+use vulkanalia::Instance;
+
+use crate::appdata::AppData;
+use crate::images::{create_image, create_image_view};
+use crate::owned::{Owned, OwnedDevice};
+
+/// The depth formats we can use, in order of preference.
+const DEPTH_FORMATS: &[vk::Format] = &[
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+/// Creates the depth image and view matching the current swapchain extent.
+pub unsafe fn create_depth_objects(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &mut AppData,
+) -> Result<()> {
+    let format = get_depth_format(instance, data)?;
+
+    let (depth_image, depth_image_memory) = create_image(
+        instance,
+        device,
+        data,
+        data.swapchain_extent.width,
+        data.swapchain_extent.height,
+        1,
+        data.msaa_samples,
+        format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    let depth_image_view = create_image_view(device, *depth_image, format, vk::ImageAspectFlags::DEPTH, 1)?;
+    data.depth_image_view = Owned::new(device, depth_image_view);
+    data.depth_image = depth_image;
+    data.depth_image_memory = depth_image_memory;
+
+    Ok(())
+}
+
+/// Picks the preferred depth format the physical device can use as an
+/// optimally tiled depth attachment.
+pub unsafe fn get_depth_format(instance: &Instance, data: &AppData) -> Result<vk::Format> {
+    get_supported_format(
+        instance,
+        data,
+        DEPTH_FORMATS,
+        vk::ImageTiling::OPTIMAL,
+        vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+    )
+}
+
+unsafe fn get_supported_format(
+    instance: &Instance,
+    data: &AppData,
+    candidates: &[vk::Format],
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+) -> Result<vk::Format> {
+    candidates
+        .iter()
+        .cloned()
+        .find(|f| {
+            let properties = instance.get_physical_device_format_properties(data.physical_device, *f);
+            match tiling {
+                vk::ImageTiling::LINEAR => properties.linear_tiling_features.contains(features),
+                vk::ImageTiling::OPTIMAL => properties.optimal_tiling_features.contains(features),
+                _ => false,
+            }
+        })
+        .ok_or_else(|| anyhow!("Failed to find supported format!"))
+}
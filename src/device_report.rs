@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::loader::{LibloadingLoader, LIBRARY};
+use vulkanalia::vk::{self, InstanceV1_0, KhrSurfaceExtension};
+use vulkanalia::{Entry, Instance, Version};
+use winit::window::Window;
+
+use crate::appdata::AppData;
+use crate::devices::{get_suitability_errors, score_physical_device};
+use crate::instance::create_instance;
+use crate::swapchain_support::SwapchainSupport;
+use crate::validation::{Validation, ValidationConfig};
+use crate::DEVICE_EXTENSIONS;
+
+/// Everything we know about a physical device that bears on whether our app
+/// can use it.
+#[derive(Clone, Debug)]
+pub struct DeviceReport {
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub api_version: Version,
+    pub driver_version: u32,
+    pub queue_families: Vec<QueueFamilyReport>,
+    pub memory_heaps: Vec<MemoryHeapReport>,
+    /// Each required device extension and whether it is supported.
+    pub extensions: Vec<(String, bool)>,
+    /// The surface formats, if there was a surface to query.
+    pub surface_formats: Option<Vec<vk::SurfaceFormatKHR>>,
+    /// The present modes, if there was a surface to query.
+    pub present_modes: Option<Vec<vk::PresentModeKHR>>,
+    pub score: u64,
+    /// Why the device is unsuitable, if it is.
+    pub unsuitable: Vec<&'static str>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct QueueFamilyReport {
+    pub flags: vk::QueueFlags,
+    pub count: u32,
+    /// Whether the family can present to our surface, if there is one.
+    pub present: Option<bool>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MemoryHeapReport {
+    pub size: vk::DeviceSize,
+    pub device_local: bool,
+}
+
+/// Reports on every physical device. With a `window`, presentation and
+/// swapchain support are checked against a surface for it.
+pub unsafe fn get_device_reports(
+    window: Option<&Window>,
+    validation: &ValidationConfig,
+) -> Result<Vec<DeviceReport>> {
+    let loader = LibloadingLoader::new(LIBRARY)?;
+    let entry = Entry::new(loader).map_err(|b| anyhow!("{}", b))?;
+    let mut data = AppData {
+        headless: window.is_none(),
+        validation: Arc::new(Validation::new(validation.clone())),
+        ..Default::default()
+    };
+    let mut instance = create_instance(window, &entry, &mut data)?;
+    if let Some(window) = window {
+        data.surface = instance.create_surface(window)?;
+    }
+
+    // The instance destroys itself and the surface once the reports are in,
+    // or as soon as one of them fails.
+    instance
+        .enumerate_physical_devices()?
+        .iter()
+        .enumerate()
+        .map(|(i, d)| get_device_report(&instance, &data, i, *d))
+        .collect()
+}
+
+unsafe fn get_device_report(
+    instance: &Instance,
+    data: &AppData,
+    index: usize,
+    physical_device: vk::PhysicalDevice,
+) -> Result<DeviceReport> {
+    let properties = instance.get_physical_device_properties(physical_device);
+    let memory = instance.get_physical_device_memory_properties(physical_device);
+
+    let mut queue_families = vec![];
+    for (i, family) in instance.get_physical_device_queue_family_properties(physical_device).iter().enumerate() {
+        let present = if data.headless {
+            None
+        } else {
+            Some(instance.get_physical_device_surface_support_khr(physical_device, i as u32, data.surface)?)
+        };
+
+        queue_families.push(QueueFamilyReport { flags: family.queue_flags, count: family.queue_count, present });
+    }
+
+    let memory_heaps = memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .map(|h| MemoryHeapReport {
+            size: h.size,
+            device_local: h.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+        })
+        .collect();
+
+    let available = instance
+        .enumerate_device_extension_properties(physical_device, None)?
+        .iter()
+        .map(|e| e.extension_name)
+        .collect::<HashSet<_>>();
+    let extensions = DEVICE_EXTENSIONS
+        .iter()
+        .map(|e| (e.to_string(), available.contains(e)))
+        .collect();
+
+    let (surface_formats, present_modes) = if data.headless {
+        (None, None)
+    } else {
+        let support = SwapchainSupport::get(instance, data, physical_device)?;
+        (Some(support.formats), Some(support.present_modes))
+    };
+
+    let unsuitable = get_suitability_errors(instance, data, physical_device)?
+        .iter()
+        .map(|e| e.0)
+        .collect();
+
+    Ok(DeviceReport {
+        index,
+        name: properties.device_name.to_string(),
+        device_type: properties.device_type,
+        vendor_id: properties.vendor_id,
+        device_id: properties.device_id,
+        api_version: Version::from(properties.api_version),
+        driver_version: properties.driver_version,
+        queue_families,
+        memory_heaps,
+        extensions,
+        surface_formats,
+        present_modes,
+        score: score_physical_device(instance, physical_device),
+        unsuitable,
+    })
+}
+
+impl fmt::Display for DeviceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[{}] {} ({:?})", self.index, self.name, self.device_type)?;
+        writeln!(f, "  Vendor/device ID: {:04x}:{:04x}", self.vendor_id, self.device_id)?;
+        writeln!(f, "  API version: {}", self.api_version)?;
+        writeln!(f, "  Driver version: {} ({:#010x})", Version::from(self.driver_version), self.driver_version)?;
+        writeln!(f, "  Score: {}", self.score)?;
+
+        writeln!(f, "  Queue families:")?;
+        for (i, family) in self.queue_families.iter().enumerate() {
+            let present = match family.present {
+                Some(true) => ", present",
+                _ => "",
+            };
+            writeln!(f, "    [{}] {:?} x{}{}", i, family.flags, family.count, present)?;
+        }
+
+        writeln!(f, "  Memory heaps:")?;
+        for (i, heap) in self.memory_heaps.iter().enumerate() {
+            let local = if heap.device_local { ", device local" } else { "" };
+            writeln!(f, "    [{}] {} MiB{}", i, heap.size / (1024 * 1024), local)?;
+        }
+
+        writeln!(f, "  Required extensions:")?;
+        for (name, supported) in &self.extensions {
+            writeln!(f, "    {}: {}", name, if *supported { "supported" } else { "missing" })?;
+        }
+
+        match (&self.surface_formats, &self.present_modes) {
+            (Some(formats), Some(modes)) => {
+                writeln!(f, "  Surface formats:")?;
+                for format in formats {
+                    writeln!(f, "    {:?} {:?}", format.format, format.color_space)?;
+                }
+                writeln!(f, "  Present modes: {:?}", modes)?;
+            }
+            _ => writeln!(f, "  Surface formats and present modes: unknown (no surface)")?,
+        }
+
+        if self.unsuitable.is_empty() {
+            writeln!(f, "  Suitable: yes")
+        } else {
+            writeln!(f, "  Suitable: no")?;
+            self.unsuitable.iter().try_for_each(|r| writeln!(f, "    {}", r))
+        }
+    }
+}
+
+impl DeviceReport {
+    /// Writes the report as a JSON object.
+    pub fn to_json(&self) -> String {
+        let queue_families = self.queue_families
+            .iter()
+            .map(|q| format!(
+                r#"{{"flags":{},"count":{},"present":{}}}"#,
+                json_string(&format!("{:?}", q.flags)),
+                q.count,
+                q.present.map(|p| p.to_string()).unwrap_or_else(|| "null".into()),
+            ))
+            .collect::<Vec<_>>();
+
+        let memory_heaps = self.memory_heaps
+            .iter()
+            .map(|h| format!(r#"{{"size":{},"device_local":{}}}"#, h.size, h.device_local))
+            .collect::<Vec<_>>();
+
+        let extensions = self.extensions
+            .iter()
+            .map(|(name, supported)| format!(r#"{{"name":{},"supported":{}}}"#, json_string(name), supported))
+            .collect::<Vec<_>>();
+
+        let surface_formats = self.surface_formats.as_ref().map(|formats| {
+            formats
+                .iter()
+                .map(|f| format!(
+                    r#"{{"format":{},"color_space":{}}}"#,
+                    json_string(&format!("{:?}", f.format)),
+                    json_string(&format!("{:?}", f.color_space)),
+                ))
+                .collect::<Vec<_>>()
+        });
+
+        let present_modes = self.present_modes.as_ref().map(|modes| {
+            modes.iter().map(|m| json_string(&format!("{:?}", m))).collect::<Vec<_>>()
+        });
+
+        let unsuitable = self.unsuitable.iter().map(|r| json_string(r)).collect::<Vec<_>>();
+
+        format!(
+            concat!(
+                "{{",
+                r#""index":{},"name":{},"type":{},"vendor_id":{},"device_id":{},"#,
+                r#""api_version":{},"driver_version":{},"score":{},"suitable":{},"#,
+                r#""unsuitable_reasons":{},"queue_families":{},"memory_heaps":{},"#,
+                r#""required_extensions":{},"surface_formats":{},"present_modes":{}"#,
+                "}}",
+            ),
+            self.index,
+            json_string(&self.name),
+            json_string(&format!("{:?}", self.device_type)),
+            self.vendor_id,
+            self.device_id,
+            json_string(&self.api_version.to_string()),
+            self.driver_version,
+            self.score,
+            self.unsuitable.is_empty(),
+            json_array(&unsuitable),
+            json_array(&queue_families),
+            json_array(&memory_heaps),
+            json_array(&extensions),
+            surface_formats.map(|f| json_array(&f)).unwrap_or_else(|| "null".into()),
+            present_modes.map(|m| json_array(&m)).unwrap_or_else(|| "null".into()),
+        )
+    }
+}
+
+/// Writes `reports` as a JSON array.
+pub fn reports_to_json(reports: &[DeviceReport]) -> String {
+    json_array(&reports.iter().map(|r| r.to_json()).collect::<Vec<_>>())
+}
+
+fn json_array(values: &[String]) -> String {
+    format!("[{}]", values.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -1,6 +1,9 @@
 use std::collections::HashSet;
+use std::sync::Arc;
 
 use crate::appdata;
+use crate::owned::OwnedDevice;
+use crate::owned::OwnedInstance;
 use crate::queue_family_indices;
 use crate::queue_family_indices::QueueFamilyIndices;
 use crate::swapchain_support::SwapchainSupport;
@@ -9,8 +12,6 @@ use crate::VALIDATION_LAYER;
 
 use super::PORTABILITY_MACOS_VERSION;
 
-use super::VALIDATION_ENABLED;
-
 use anyhow::Ok;
 use anyhow::{anyhow, Result};
 use log::info;
@@ -20,7 +21,6 @@ use vulkanalia::vk;
 use vulkanalia::vk::DeviceV1_0;
 use vulkanalia::vk::HasBuilder;
 use vulkanalia::vk::InstanceV1_0;
-use vulkanalia::Device;
 use vulkanalia::Entry;
 use vulkanalia::Instance;
 
@@ -28,28 +28,95 @@ use vulkanalia::Instance;
 #[error("Missing {0}.")]
 pub struct SuitabilityError(pub &'static str);
 
-pub unsafe fn pick_physical_device(instance: &Instance, data: &mut appdata::AppData) -> Result<()> {
+/// Picks the suitable physical device with the highest score, or the one
+/// matching `selector` (an index or a case-insensitive name substring).
+pub unsafe fn pick_physical_device(
+    instance: &Instance,
+    data: &mut appdata::AppData,
+    selector: Option<&str>,
+) -> Result<()> {
+    let physical_devices = instance.enumerate_physical_devices()?;
+
+    if let Some(selector) = selector {
+        let index = selector.parse::<usize>().ok();
+        let matches = |i: usize, physical_device: vk::PhysicalDevice| {
+            let name = instance.get_physical_device_properties(physical_device).device_name.to_string();
+            index == Some(i) || (index.is_none() && name.to_lowercase().contains(&selector.to_lowercase()))
+        };
+
+        let (_, physical_device) = physical_devices
+            .iter()
+            .copied()
+            .enumerate()
+            .find(|(i, d)| matches(*i, *d))
+            .ok_or_else(|| anyhow!("No physical device matches `{}`.", selector))?;
+
+        let properties = instance.get_physical_device_properties(physical_device);
+        check_physical_device(instance, data, physical_device)
+            .map_err(|e| anyhow!("Physical device (`{}`) is unsuitable: {}", properties.device_name, e))?;
 
-    let mut found: bool = false;
-    for physical_device in instance.enumerate_physical_devices()? {
+        info!("Selected physical device (`{}`) matching `{}`.", properties.device_name, selector);
+        data.physical_device = physical_device;
+        return Ok(());
+    }
+
+    let mut best = None;
+    for physical_device in physical_devices {
         let properties = instance.get_physical_device_properties(physical_device);
 
         if let Err(error) = check_physical_device(instance, data, physical_device) {
             warn!("Skipping physical device (`{}`): {}", properties.device_name, error);
-        } else if !found 
-            || instance.get_physical_device_properties(data.physical_device).limits.max_compute_shared_memory_size < properties.limits.max_compute_shared_memory_size 
-            {
-            info!("Selected physical device (`{}`).", properties.device_name);
-            data.physical_device = physical_device;
-            found = true;
+            continue;
         }
-    }
 
-    if found {
-        return Ok(());
+        let score = score_physical_device(instance, physical_device);
+        info!("Physical device (`{}`) scored {}.", properties.device_name, score);
+        if best.map(|(s, _)| score > s).unwrap_or(true) {
+            best = Some((score, physical_device));
+        }
     }
 
-    Err(anyhow!("Failed to find suitable physical device."))
+    let (_, physical_device) = best.ok_or_else(|| anyhow!("Failed to find suitable physical device."))?;
+    let properties = instance.get_physical_device_properties(physical_device);
+    info!("Selected physical device (`{}`).", properties.device_name);
+    data.physical_device = physical_device;
+
+    Ok(())
+}
+
+/// Ranks a physical device by its type first, preferring discrete GPUs over
+/// integrated ones over CPU implementations, then by its device-local
+/// memory and the optional features we can use.
+pub unsafe fn score_physical_device(instance: &Instance, physical_device: vk::PhysicalDevice) -> u64 {
+    let properties = instance.get_physical_device_properties(physical_device);
+    let memory = instance.get_physical_device_memory_properties(physical_device);
+    let features = instance.get_physical_device_features(physical_device);
+
+    let rank = match properties.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    };
+
+    // In MiB, so no realistic amount outweighs the device type.
+    let local_memory = memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .filter(|h| h.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|h| h.size / (1024 * 1024))
+        .sum::<u64>()
+        .min(999_999);
+
+    // Each feature is worth as much as a GiB of memory.
+    let optional_features = [
+        features.sampler_anisotropy,
+        features.sample_rate_shading,
+        features.fill_mode_non_solid,
+    ];
+    let feature_count = optional_features.iter().filter(|f| **f == vk::TRUE).count() as u64;
+
+    rank * 10_000_000 + local_memory + feature_count * 1024
 }
 
 pub unsafe fn check_physical_device(
@@ -57,15 +124,39 @@ pub unsafe fn check_physical_device(
     data: &appdata::AppData,
     physical_device: vk::PhysicalDevice,
 ) -> Result<()> {
-    queue_family_indices::QueueFamilyIndices::get(instance, data, physical_device)?;
-    check_physical_device_extensions(instance, physical_device)?;
+    match get_suitability_errors(instance, data, physical_device)?.into_iter().next() {
+        Some(error) => Err(anyhow!(error)),
+        None => Ok(()),
+    }
+}
+
+/// Returns every reason `physical_device` cannot run our app, rather than
+/// stopping at the first like `check_physical_device`.
+pub unsafe fn get_suitability_errors(
+    instance: &Instance,
+    data: &appdata::AppData,
+    physical_device: vk::PhysicalDevice,
+) -> Result<Vec<SuitabilityError>> {
+    let mut errors = vec![];
+
+    if let Err(error) = queue_family_indices::QueueFamilyIndices::get(instance, data, physical_device) {
+        errors.push(error.downcast::<SuitabilityError>()?);
+    }
+
+    if data.headless {
+        return Ok(errors);
+    }
+
+    if let Err(error) = check_physical_device_extensions(instance, physical_device) {
+        errors.push(error.downcast::<SuitabilityError>()?);
+    }
 
     let support = SwapchainSupport::get(instance, data, physical_device)?;
     if support.formats.is_empty() || support.present_modes.is_empty() {
-        return Err(anyhow!(SuitabilityError("Insufficient swapchain support.")));
+        errors.push(SuitabilityError("Insufficient swapchain support."));
     }
-    
-    Ok(())
+
+    Ok(errors)
 }
 
 unsafe fn check_physical_device_extensions(
@@ -88,9 +179,9 @@ unsafe fn check_physical_device_extensions(
 
 pub unsafe fn create_logical_device(
     entry: &Entry,
-    instance: &Instance,
+    instance: &Arc<OwnedInstance>,
     data: &mut appdata::AppData,
-) -> Result<Device> 
+) -> Result<Arc<OwnedDevice>> 
 {
     let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
 
@@ -108,23 +199,33 @@ pub unsafe fn create_logical_device(
         })
         .collect::<Vec<_>>();
 
-    let layers = if VALIDATION_ENABLED {
+    let layers = if data.validation.config.enabled {
         vec![VALIDATION_LAYER.as_ptr()]
     } else {
         vec![]
     };
 
-    let mut extensions = DEVICE_EXTENSIONS
-        .iter()
-        .map(|n| n.as_ptr())
-        .collect::<Vec<_>>();
+    let mut extensions = if data.headless {
+        vec![]
+    } else {
+        DEVICE_EXTENSIONS
+            .iter()
+            .map(|n| n.as_ptr())
+            .collect::<Vec<_>>()
+    };
 
     // Required by Vulkan SDK on macOS since 1.3.216.
     if cfg!(target_os = "macos") && entry.version()? >= PORTABILITY_MACOS_VERSION {
         extensions.push(vk::KHR_PORTABILITY_SUBSET_EXTENSION.name.as_ptr());
     }
 
-    let features = vk::PhysicalDeviceFeatures::builder();
+    // Optional features are enabled whenever the device has them, and
+    // remembered so the rest of the app can tell whether it may use them.
+    let supported = instance.get_physical_device_features(data.physical_device);
+    let features = vk::PhysicalDeviceFeatures::builder()
+        .sampler_anisotropy(supported.sampler_anisotropy == vk::TRUE)
+        .sample_rate_shading(supported.sample_rate_shading == vk::TRUE)
+        .fill_mode_non_solid(supported.fill_mode_non_solid == vk::TRUE);
 
     let info = vk::DeviceCreateInfo::builder()        
     .queue_create_infos(&queue_infos)
@@ -133,6 +234,8 @@ pub unsafe fn create_logical_device(
     .enabled_features(&features);
 
     let device = instance.create_device(data.physical_device, &info, None)?;
+    let device = Arc::new(OwnedDevice::new(device, instance.clone()));
+    data.enabled_features = features.build();
 
     data.graphics_queue = device.get_device_queue(indices.graphics, 0);
     data.present_queue = device.get_device_queue(indices.present, 0);
@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::Arc;
+use std::path::Path;
+
+use anyhow::Result;
+use vulkanalia::vk::{self, DeviceV1_0, HasBuilder};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+use crate::memory::get_memory_type_index;
+use crate::owned::{Owned, OwnedDevice};
+
+/// The format of the offscreen color image, chosen so the read back bytes
+/// can be written to a PNG as-is.
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Creates the offscreen color image that stands in for the swapchain in
+/// headless mode, along with a host-visible buffer it is copied into.
+pub unsafe fn create_offscreen_target(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &mut AppData,
+    extent: vk::Extent2D,
+) -> Result<()> {
+    let info = vk::ImageCreateInfo::builder()
+        .image_type(vk::ImageType::_2D)
+        .extent(vk::Extent3D { width: extent.width, height: extent.height, depth: 1 })
+        .mip_levels(1)
+        .array_layers(1)
+        .format(OFFSCREEN_FORMAT)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .samples(vk::SampleCountFlags::_1);
+
+    let image = device.create_image(&info, None)?;
+    data.offscreen_image = Owned::new(device, image);
+
+    let requirements = device.get_image_memory_requirements(image);
+    let info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(requirements.size)
+        .memory_type_index(get_memory_type_index(
+            instance,
+            data,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            requirements,
+        )?);
+
+    data.offscreen_image_memory = Owned::new(device, device.allocate_memory(&info, None)?);
+    device.bind_image_memory(image, *data.offscreen_image_memory, 0)?;
+
+    let info = vk::BufferCreateInfo::builder()
+        .size(readback_size(extent))
+        .usage(vk::BufferUsageFlags::TRANSFER_DST)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+    data.readback_buffer = Owned::new(device, device.create_buffer(&info, None)?);
+
+    let requirements = device.get_buffer_memory_requirements(*data.readback_buffer);
+    let info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(requirements.size)
+        .memory_type_index(get_memory_type_index(
+            instance,
+            data,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            requirements,
+        )?);
+
+    data.readback_buffer_memory = Owned::new(device, device.allocate_memory(&info, None)?);
+    device.bind_buffer_memory(*data.readback_buffer, *data.readback_buffer_memory, 0)?;
+
+    data.swapchain_images = vec![image];
+    data.swapchain_format = OFFSCREEN_FORMAT;
+    data.swapchain_extent = extent;
+
+    Ok(())
+}
+
+/// Records a copy of the rendered offscreen image into the readback buffer.
+///
+/// The render pass leaves the image in `TRANSFER_SRC_OPTIMAL` in headless mode.
+pub unsafe fn record_readback(device: &Device, data: &AppData, command_buffer: vk::CommandBuffer) {
+    let subresource = vk::ImageSubresourceLayers::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .mip_level(0)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(subresource)
+        .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+        .image_extent(vk::Extent3D {
+            width: data.swapchain_extent.width,
+            height: data.swapchain_extent.height,
+            depth: 1,
+        });
+
+    device.cmd_copy_image_to_buffer(
+        command_buffer,
+        data.swapchain_images[0],
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        *data.readback_buffer,
+        &[region],
+    );
+
+    let barrier = vk::BufferMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(*data.readback_buffer)
+        .offset(0)
+        .size(vk::WHOLE_SIZE as vk::DeviceSize);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::HOST,
+        vk::DependencyFlags::empty(),
+        &[] as &[vk::MemoryBarrier],
+        &[barrier],
+        &[] as &[vk::ImageMemoryBarrier],
+    );
+}
+
+/// Copies the RGBA8 pixels of the last rendered frame out of the readback
+/// buffer. The frame must have finished executing.
+pub unsafe fn read_pixels(device: &Device, data: &AppData) -> Result<Vec<u8>> {
+    let size = readback_size(data.swapchain_extent);
+    let memory = device.map_memory(
+        *data.readback_buffer_memory,
+        0,
+        size,
+        vk::MemoryMapFlags::empty(),
+    )?;
+
+    let pixels = std::slice::from_raw_parts(memory.cast::<u8>(), size as usize).to_vec();
+    device.unmap_memory(*data.readback_buffer_memory);
+
+    Ok(pixels)
+}
+
+/// Writes tightly packed RGBA8 pixels to a PNG file.
+pub fn save_png(path: &Path, extent: vk::Extent2D, pixels: &[u8]) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, extent.width, extent.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+fn readback_size(extent: vk::Extent2D) -> vk::DeviceSize {
+    extent.width as vk::DeviceSize * extent.height as vk::DeviceSize * 4
+}
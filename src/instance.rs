@@ -5,28 +5,28 @@ use vulkanalia::vk;
 use vulkanalia::vk::EntryV1_0;
 use vulkanalia::vk::HasBuilder;
 use vulkanalia::Entry;
-use vulkanalia::Instance;
 use vulkanalia::window as vk_window;
 use crate::appdata;
+use crate::owned::OwnedInstance;
 
 use super::debug_callback;
 
 use super::VALIDATION_LAYER;
 
 use std::collections::HashSet;
+use std::ffi::c_void;
+use std::sync::Arc;
 
 use super::PORTABILITY_MACOS_VERSION;
 
-use super::VALIDATION_ENABLED;
-
 
 use winit::window::Window;
 
 pub unsafe fn create_instance(
-    window: &Window,
+    window: Option<&Window>,
     entry: &Entry,
     data: &mut appdata::AppData
-) -> Result<Instance>
+) -> Result<OwnedInstance>
 {
     let api_version = vk::ApplicationInfo::builder()
         .application_name(b"Vulkan Tutorial\0")
@@ -36,12 +36,15 @@ pub unsafe fn create_instance(
         .api_version(vk::make_version(1, 0, 0));
     let application_info = api_version;
 
-    let mut extensions = vk_window::get_required_instance_extensions(window)
+    // Headless rendering needs no surface, and therefore no WSI extensions.
+    let mut extensions = window
+        .map(|w| vk_window::get_required_instance_extensions(w))
+        .unwrap_or(&[])
         .iter()
         .map(|e| e.as_ptr())
         .collect::<Vec<_>>();
 
-    if VALIDATION_ENABLED 
+    if data.validation.config.enabled
     {
         extensions.push(vk::EXT_DEBUG_UTILS_EXTENSION.name.as_ptr());
     }
@@ -66,12 +69,12 @@ pub unsafe fn create_instance(
         .map(|l| l.layer_name)
         .collect::<HashSet<_>>();
 
-    if VALIDATION_ENABLED && !available_layers.contains(&VALIDATION_LAYER) 
+    if data.validation.config.enabled && !available_layers.contains(&VALIDATION_LAYER) 
     {
         return Err(anyhow!("Validation layer requested but not supported."));
     }
 
-    let layers = if VALIDATION_ENABLED 
+    let layers = if data.validation.config.enabled
     {
         vec![VALIDATION_LAYER.as_ptr()]
     } else {
@@ -86,22 +89,41 @@ pub unsafe fn create_instance(
         .flags(flags);
 
 
-    let mut debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-        .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::all())
-        .message_type(
-            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-        )
-        .user_callback(Some(debug_callback));
+    // This messenger only covers instance creation and destruction; the
+    // one created below covers everything in between.
+    let mut debug_info = get_messenger_info(data);
 
-    if VALIDATION_ENABLED 
+    if data.validation.config.enabled
     {
         info = info.push_next(&mut debug_info);
     }
 
 
-    let instance = entry.create_instance(&info, None)?;
+    let mut instance = OwnedInstance::new(
+        entry.create_instance(&info, None)?,
+        data.validation.clone(),
+    );
+
+    if data.validation.config.enabled
+    {
+        instance.create_messenger(&debug_info)?;
+    }
 
     Ok(instance)
+}
+
+/// Describes a debug messenger reporting to `debug_callback` with our
+/// validation filters.
+fn get_messenger_info(data: &appdata::AppData) -> vk::DebugUtilsMessengerCreateInfoEXT {
+    let mut info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
+        .message_severity(data.validation.config.severity)
+        .message_type(data.validation.config.types)
+        .user_callback(Some(debug_callback))
+        .build();
+
+    // The callback reads the suppression list and records messages through
+    // the user data, which the instance keeps alive for as long as it lives.
+    info.user_data = Arc::as_ptr(&data.validation) as *mut c_void;
+
+    info
 }
\ No newline at end of file
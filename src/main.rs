@@ -7,15 +7,39 @@
 
 mod app;
 mod appdata;
+mod buffers;
+mod commands;
+mod config;
+mod debug;
+mod depth;
+mod device_report;
+mod headless;
+mod memory;
+mod images;
+mod mesh;
+mod model;
+mod msaa;
+mod owned;
+mod vertex;
 mod queue_family_indices;
 mod devices;
 mod instance;
 mod swapchain_support;
 mod swapchain;
+mod texture;
+mod uniforms;
+mod validation;
 mod pipeline;
+mod pipeline_cache;
+mod push_constants;
+mod reflect;
+mod shaders;
 
 use anyhow::{Ok, Result};
 use app::App;
+use config::Config;
+use device_report::{get_device_reports, reports_to_json};
+use validation::Validation;
 use log::*;
 
 use vulkanalia::prelude::v1_0::*;
@@ -31,38 +55,70 @@ use std::os::raw::c_void;
 
 const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
 
-const VALIDATION_ENABLED: bool = cfg!(debug_assertions);
 const VALIDATION_LAYER: vk::ExtensionName = vk::ExtensionName::from_bytes(b"VK_LAYER_KHRONOS_validation");
 
 const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[vk::KHR_SWAPCHAIN_EXTENSION.name];
 
+/// The maximum number of frames the CPU may record ahead of the GPU.
+const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
 
 
 fn main() -> Result<()> {
     pretty_env_logger::init();
 
+    let config = Config::from_args()?;
+
+    if config.list_devices {
+        return list_devices(&config);
+    }
+
+    if config.headless {
+        return run_headless(&config);
+    }
+
     // Window
 
     let event_loop = EventLoop::new()?;
     let window = WindowBuilder::new()
         .with_title("Vulkan Tutorial (Rust)")
-        .with_inner_size(LogicalSize::new(1024, 768))
+        .with_inner_size(LogicalSize::new(config.extent.width, config.extent.height))
         .build(&event_loop)?;
 
     // App
 
-    let mut app = unsafe { App::create(&window)? };
+    // Taken out when the window closes, so it is destroyed before the event
+    // loop returns.
+    let mut app = Some(unsafe { App::create(&window, &config)? });
+    let mut minimized = false;
     event_loop.run(move |event, elwt| {
         match event {
             // Request a redraw when all events were processed.
             Event::AboutToWait => window.request_redraw(),
             Event::WindowEvent { event, .. } => match event {
                 // Render a frame if our Vulkan app is not being destroyed.
-                WindowEvent::RedrawRequested if !elwt.exiting() => unsafe { app.render(&window) }.unwrap(),
+                WindowEvent::RedrawRequested if !elwt.exiting() && !minimized => {
+                    if let Some(app) = &mut app {
+                        unsafe { app.render(&window) }.unwrap();
+                    }
+                }
+                // Pause rendering while the window has no area to draw into.
+                WindowEvent::Resized(size) => {
+                    if size.width == 0 || size.height == 0 {
+                        minimized = true;
+                    } else {
+                        minimized = false;
+                        if let Some(app) = &mut app {
+                            app.resized = true;
+                        }
+                    }
+                }
                 // Destroy our Vulkan app.
                 WindowEvent::CloseRequested => {
                     elwt.exit();
-                    unsafe { app.destroy(); }
+                    if let Some(app) = app.take() {
+                        app.destroy().unwrap();
+                    }
                 }
                 _ => {}
             }
@@ -73,27 +129,103 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Prints a report on every physical device, including why any of them
+/// cannot run our app.
+fn list_devices(config: &Config) -> Result<()> {
+    // A hidden window gives us a surface to check presentation and swapchain
+    // support against. Without a display, those checks are left out.
+    let event_loop = EventLoop::new().ok();
+    let window = event_loop
+        .as_ref()
+        .and_then(|e| WindowBuilder::new().with_visible(false).build(e).ok());
+
+    let reports = unsafe { get_device_reports(window.as_ref(), &config.validation)? };
+    if config.json {
+        println!("{}", reports_to_json(&reports));
+    } else {
+        reports.iter().for_each(|r| print!("{}", r));
+    }
+
+    Ok(())
+}
+
+/// Renders the configured number of frames without a window and writes
+/// each one to the output directory as a PNG.
+fn run_headless(config: &Config) -> Result<()> {
+    std::fs::create_dir_all(&config.output)?;
+
+    let mut app = unsafe { App::create_headless(config)? };
+    for frame in 0..config.frames {
+        let path = config.output.join(format!("frame_{:04}.png", frame));
+        unsafe { app.render_headless(&path)? };
+        info!("Wrote `{}`.", path.display());
+    }
+    let validation = app.data.validation.clone();
+    let result = app.destroy();
+
+    info!(
+        "Validation reported {} errors and {} warnings.",
+        validation.get_error_count(),
+        validation.get_warning_count(),
+    );
+
+    result
+}
 
 extern "system" fn debug_callback(
     severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     type_: vk::DebugUtilsMessageTypeFlagsEXT,
     data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _: *mut c_void,
+    user_data: *mut c_void,
 ) -> vk::Bool32 {
     let data = unsafe { *data };
     let message = unsafe { CStr::from_ptr(data.message) }.to_string_lossy();
+    let id_name = (!data.message_id_name.is_null())
+        .then(|| unsafe { CStr::from_ptr(data.message_id_name) }.to_string_lossy());
+
+    let id = match &id_name {
+        Some(name) => name.to_string(),
+        None => format!("{:#010x}", data.message_id_number),
+    };
+
+    // The user data is the `Validation` the messenger was created with.
+    if let Some(validation) = unsafe { (user_data as *const Validation).as_ref() } {
+        if validation.config.is_suppressed(id_name.as_deref(), data.message_id_number) {
+            return vk::FALSE;
+        }
+
+        validation.record(&id, severity);
+    }
 
     if severity >= vk::DebugUtilsMessageSeverityFlagsEXT::ERROR {
-        error!("({:?}) {}", type_, message);
+        error!("({:?}) [{}] {}", type_, id, message);
     } else if severity >= vk::DebugUtilsMessageSeverityFlagsEXT::WARNING {
-        warn!("({:?}) {}", type_, message);
+        warn!("({:?}) [{}] {}", type_, id, message);
     } else if severity >= vk::DebugUtilsMessageSeverityFlagsEXT::INFO {
-        debug!("({:?}) {}", type_, message);
+        debug!("({:?}) [{}] {}", type_, id, message);
     } else {
-        trace!("({:?}) {}", type_, message);
+        trace!("({:?}) [{}] {}", type_, id, message);
     }
 
     vk::FALSE
 }
 
-    
\ No newline at end of file
+    
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "needs a Vulkan driver and the validation layer"]
+    fn test_headless_run_has_no_validation_errors() {
+        let output = std::env::temp_dir().join("vulkan_playground_test");
+        let args = ["--headless", "--frames", "2", "--validation", "--fail-fast", "error", "--output"]
+            .iter()
+            .map(|a| a.to_string())
+            .chain([output.display().to_string()]);
+        let config = Config::parse(args).unwrap();
+
+        // Fail-fast mode turns any validation error into an error here.
+        run_headless(&config).unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::{anyhow, Result};
+use vulkanalia::vk::{self, InstanceV1_0};
+use vulkanalia::Instance;
+
+use crate::appdata::AppData;
+
+/// Finds a memory type on the picked physical device that is allowed by
+/// `requirements` and has all of `properties`.
+pub unsafe fn get_memory_type_index(
+    instance: &Instance,
+    data: &AppData,
+    properties: vk::MemoryPropertyFlags,
+    requirements: vk::MemoryRequirements,
+) -> Result<u32> {
+    let memory = instance.get_physical_device_memory_properties(data.physical_device);
+
+    (0..memory.memory_type_count)
+        .find(|i| {
+            let suitable = (requirements.memory_type_bits & (1 << i)) != 0;
+            let memory_type = memory.memory_types[*i as usize];
+            suitable && memory_type.property_flags.contains(properties)
+        })
+        .ok_or_else(|| anyhow!("Failed to find suitable memory type."))
+}
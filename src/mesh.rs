@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use cgmath::SquareMatrix;
+use vulkanalia::vk::{self, DeviceV1_0};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+use crate::buffers::create_device_local_buffer;
+use crate::owned::{Destroy, OwnedDevice};
+use crate::push_constants::{push_constants, MeshPushConstants};
+use crate::uniforms::Mat4;
+use crate::vertex::{Vec2, Vec3, Vertex};
+
+/// The geometry drawn when no model is loaded.
+pub const QUAD_VERTICES: [Vertex; 4] = [
+    Vertex::new(Vec3::new(-0.5, -0.5, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(1.0, 0.0)),
+    Vertex::new(Vec3::new(0.5, -0.5, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 0.0)),
+    Vertex::new(Vec3::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 1.0)),
+    Vertex::new(Vec3::new(-0.5, 0.5, 0.0), Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(1.0, 1.0)),
+];
+
+pub const QUAD_INDICES: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
+/// Geometry uploaded to device-local memory, ready to be drawn.
+#[derive(Copy, Clone, Debug)]
+pub struct Mesh {
+    pub vertex_buffer: vk::Buffer,
+    pub vertex_buffer_memory: vk::DeviceMemory,
+    pub index_buffer: vk::Buffer,
+    pub index_buffer_memory: vk::DeviceMemory,
+    pub index_count: u32,
+    pub index_type: vk::IndexType,
+    /// The index into `AppData::materials`, if the mesh uses a material.
+    pub material: Option<usize>,
+    /// The index into `AppData::textures` sampled when drawing the mesh.
+    pub texture: usize,
+    /// The index into `AppData::pipelines` the mesh is drawn with.
+    pub pipeline: usize,
+    /// The mesh's transform within the model, pushed with each draw.
+    pub transform: Mat4,
+}
+
+impl Mesh {
+    /// Uploads `vertices` and `indices`, storing the indices as `u16` when
+    /// every vertex can be addressed by one.
+    pub unsafe fn create(
+        instance: &Instance,
+        device: &Arc<OwnedDevice>,
+        data: &AppData,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Result<Self> {
+        if vertices.is_empty() || indices.is_empty() {
+            return Err(anyhow!("Cannot create a mesh without vertices and indices."));
+        }
+
+        if let Some(index) = indices.iter().find(|i| **i as usize >= vertices.len()) {
+            return Err(anyhow!("Index {} is out of range for {} vertices.", index, vertices.len()));
+        }
+
+        let (vertex_buffer, vertex_buffer_memory) = create_device_local_buffer(
+            instance,
+            device,
+            data,
+            vertices,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+
+        let index_type = get_index_type(vertices.len());
+        let (index_buffer, index_buffer_memory) = if index_type == vk::IndexType::UINT16 {
+            let indices = indices.iter().map(|i| *i as u16).collect::<Vec<_>>();
+            create_device_local_buffer(instance, device, data, &indices, vk::BufferUsageFlags::INDEX_BUFFER)?
+        } else {
+            create_device_local_buffer(instance, device, data, indices, vk::BufferUsageFlags::INDEX_BUFFER)?
+        };
+
+        Ok(Self {
+            vertex_buffer: vertex_buffer.into_inner(),
+            vertex_buffer_memory: vertex_buffer_memory.into_inner(),
+            index_buffer: index_buffer.into_inner(),
+            index_buffer_memory: index_buffer_memory.into_inner(),
+            index_count: indices.len() as u32,
+            index_type,
+            material: None,
+            texture: 0,
+            pipeline: 0,
+            transform: Mat4::identity(),
+        })
+    }
+
+    /// Records the commands to bind and draw this mesh with a pipeline
+    /// using `layout`.
+    pub unsafe fn record_draw(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+    ) {
+        let constants = MeshPushConstants {
+            transform: self.transform,
+            material_id: self.material.map(|m| m as u32).unwrap_or(u32::MAX),
+        };
+
+        push_constants(device, command_buffer, layout, &constants);
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer], &[0]);
+        device.cmd_bind_index_buffer(command_buffer, self.index_buffer, 0, self.index_type);
+        device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+    }
+}
+
+impl Destroy for Mesh {
+    unsafe fn destroy(&self, device: &Device) {
+        device.destroy_buffer(self.index_buffer, None);
+        device.free_memory(self.index_buffer_memory, None);
+        device.destroy_buffer(self.vertex_buffer, None);
+        device.free_memory(self.vertex_buffer_memory, None);
+    }
+}
+
+/// Picks the smallest index type able to address `vertex_count` vertices.
+pub fn get_index_type(vertex_count: usize) -> vk::IndexType {
+    if vertex_count <= u16::MAX as usize + 1 {
+        vk::IndexType::UINT16
+    } else {
+        vk::IndexType::UINT32
+    }
+}
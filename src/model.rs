@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+
+use crate::vertex::{Vec2, Vec3, Vertex};
+
+/// The CPU-side contents of a Wavefront OBJ file.
+#[derive(Clone, Debug, Default)]
+pub struct Model {
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<Material>,
+}
+
+/// One object or group of an OBJ file, with deduplicated vertices.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// The index into `Model::materials`, if the group uses a material.
+    pub material: Option<usize>,
+}
+
+/// The parts of an MTL material our app understands.
+#[derive(Clone, Debug, Default)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: [f32; 3],
+    /// The diffuse texture, resolved relative to the OBJ file.
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+/// Loads an OBJ file and the MTL files it references.
+pub fn load_model(path: &Path) -> Result<Model> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    };
+
+    let (models, materials) = tobj::load_obj(path, &options)
+        .map_err(|e| anyhow!("Failed to load model `{}`: {}.", path.display(), e))?;
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let materials = match materials {
+        Ok(materials) => materials
+            .into_iter()
+            .map(|m| Material {
+                name: m.name,
+                diffuse: m.diffuse.unwrap_or([1.0, 1.0, 1.0]),
+                diffuse_texture: m.diffuse_texture.map(|t| directory.join(t)),
+            })
+            .collect::<Vec<_>>(),
+        // A missing MTL file only costs us colors and textures.
+        Err(tobj::LoadError::OpenFileFailed) => {
+            warn!("Could not open the materials of model `{}`.", path.display());
+            vec![]
+        }
+        Err(e) => return Err(anyhow!("Failed to load materials of model `{}`: {}.", path.display(), e)),
+    };
+
+    if models.is_empty() {
+        return Err(anyhow!("Model `{}` contains no geometry.", path.display()));
+    }
+
+    let meshes = models
+        .into_iter()
+        .map(|m| load_mesh(path, m, &materials))
+        .collect::<Result<Vec<_>>>()?;
+
+    info!(
+        "Loaded model `{}` ({} meshes, {} materials).",
+        path.display(),
+        meshes.len(),
+        materials.len(),
+    );
+
+    Ok(Model { meshes, materials })
+}
+
+fn load_mesh(path: &Path, model: tobj::Model, materials: &[Material]) -> Result<MeshData> {
+    let mesh = &model.mesh;
+    let vertex_count = mesh.positions.len() / 3;
+
+    if mesh.indices.is_empty() {
+        return Err(anyhow!("Mesh `{}` of model `{}` has no faces.", model.name, path.display()));
+    }
+
+    // With `single_index` every attribute is indexed like the positions, so
+    // any attribute that is present must cover every vertex.
+    for (attribute, len, width) in [
+        ("normals", mesh.normals.len(), 3),
+        ("texture coordinates", mesh.texcoords.len(), 2),
+        ("vertex colors", mesh.vertex_color.len(), 3),
+    ] {
+        if len != 0 && len != vertex_count * width {
+            return Err(anyhow!(
+                "Mesh `{}` of model `{}` has {} {} for {} vertices.",
+                model.name,
+                path.display(),
+                len / width,
+                attribute,
+                vertex_count,
+            ));
+        }
+    }
+
+    let material = match mesh.material_id {
+        Some(id) if id >= materials.len() => {
+            warn!("Mesh `{}` references missing material {}.", model.name, id);
+            None
+        }
+        id => id,
+    };
+
+    let color = material.map(|m| materials[m].diffuse).unwrap_or([1.0, 1.0, 1.0]);
+
+    let mut unique_vertices = HashMap::new();
+    let mut vertices = vec![];
+    let mut indices = Vec::with_capacity(mesh.indices.len());
+
+    for index in &mesh.indices {
+        let i = *index as usize;
+        let vertex = Vertex::new(
+            Vec3::new(mesh.positions[3 * i], mesh.positions[3 * i + 1], mesh.positions[3 * i + 2]),
+            if mesh.vertex_color.is_empty() {
+                Vec3::from(color)
+            } else {
+                Vec3::new(mesh.vertex_color[3 * i], mesh.vertex_color[3 * i + 1], mesh.vertex_color[3 * i + 2])
+            },
+            if mesh.normals.is_empty() {
+                Vec3::new(0.0, 0.0, 0.0)
+            } else {
+                Vec3::new(mesh.normals[3 * i], mesh.normals[3 * i + 1], mesh.normals[3 * i + 2])
+            },
+            if mesh.texcoords.is_empty() {
+                Vec2::new(0.0, 0.0)
+            } else {
+                // OBJ puts the origin of texture space at the bottom left, Vulkan at the top left.
+                Vec2::new(mesh.texcoords[2 * i], 1.0 - mesh.texcoords[2 * i + 1])
+            },
+        );
+
+        let index = *unique_vertices.entry(vertex).or_insert_with(|| {
+            vertices.push(vertex);
+            vertices.len() as u32 - 1
+        });
+
+        indices.push(index);
+    }
+
+    Ok(MeshData { name: model.name, vertices, indices, material })
+}
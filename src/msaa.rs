@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::info;
+use vulkanalia::vk::{self, InstanceV1_0};
+use vulkanalia::Instance;
+
+use crate::appdata::AppData;
+use crate::images::{create_image, create_image_view};
+use crate::owned::{Owned, OwnedDevice};
+
+/// Picks the highest sample count that is no more than `requested` and
+/// supported for both color and depth attachments on the picked device.
+pub unsafe fn get_msaa_samples(
+    instance: &Instance,
+    data: &AppData,
+    requested: vk::SampleCountFlags,
+) -> vk::SampleCountFlags {
+    let limits = instance.get_physical_device_properties(data.physical_device).limits;
+    let supported = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+    let samples = [
+        vk::SampleCountFlags::_64,
+        vk::SampleCountFlags::_32,
+        vk::SampleCountFlags::_16,
+        vk::SampleCountFlags::_8,
+        vk::SampleCountFlags::_4,
+        vk::SampleCountFlags::_2,
+    ]
+    .iter()
+    .cloned()
+    .find(|s| s.bits() <= requested.bits() && supported.contains(*s))
+    .unwrap_or(vk::SampleCountFlags::_1);
+
+    if samples != requested {
+        info!("Requested {:?} samples, using {:?}.", requested, samples);
+    }
+
+    samples
+}
+
+/// Creates the multisampled color image that is rendered into and then
+/// resolved into the swapchain image. Not needed without multisampling.
+pub unsafe fn create_color_objects(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &mut AppData,
+) -> Result<()> {
+    if data.msaa_samples == vk::SampleCountFlags::_1 {
+        return Ok(());
+    }
+
+    let (color_image, color_image_memory) = create_image(
+        instance,
+        device,
+        data,
+        data.swapchain_extent.width,
+        data.swapchain_extent.height,
+        1,
+        data.msaa_samples,
+        data.swapchain_format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    let color_image_view = create_image_view(
+        device,
+        *color_image,
+        data.swapchain_format,
+        vk::ImageAspectFlags::COLOR,
+        1,
+    )?;
+    data.color_image_view = Owned::new(device, color_image_view);
+    data.color_image = color_image;
+    data.color_image_memory = color_image_memory;
+
+    Ok(())
+}
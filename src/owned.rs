@@ -0,0 +1,192 @@
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use anyhow::Result;
+use vulkanalia::vk::{self, DeviceV1_0, ExtDebugUtilsExtension, Handle, InstanceV1_0, KhrSurfaceExtension, KhrSwapchainExtension};
+use vulkanalia::window as vk_window;
+use vulkanalia::{Device, Instance};
+use winit::window::Window;
+
+use crate::validation::Validation;
+
+/// An `Instance` destroyed when dropped, along with the debug messenger and
+/// surface created for it.
+#[derive(Debug)]
+pub struct OwnedInstance {
+    instance: Instance,
+    messenger: vk::DebugUtilsMessengerEXT,
+    surface: vk::SurfaceKHR,
+    /// The debug callback's user data, kept alive until the messenger and
+    /// instance are gone.
+    validation: Arc<Validation>,
+}
+
+impl OwnedInstance {
+    pub fn new(instance: Instance, validation: Arc<Validation>) -> Self {
+        Self {
+            instance,
+            messenger: vk::DebugUtilsMessengerEXT::null(),
+            surface: vk::SurfaceKHR::null(),
+            validation,
+        }
+    }
+
+    pub unsafe fn create_messenger(
+        &mut self,
+        info: &vk::DebugUtilsMessengerCreateInfoEXT,
+    ) -> Result<vk::DebugUtilsMessengerEXT> {
+        self.messenger = self.instance.create_debug_utils_messenger_ext(info, None)?;
+        Ok(self.messenger)
+    }
+
+    pub unsafe fn create_surface(&mut self, window: &Window) -> Result<vk::SurfaceKHR> {
+        self.surface = vk_window::create_surface(&self.instance, &window, &window)?;
+        Ok(self.surface)
+    }
+}
+
+impl Deref for OwnedInstance {
+    type Target = Instance;
+
+    fn deref(&self) -> &Instance {
+        &self.instance
+    }
+}
+
+impl Drop for OwnedInstance {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.surface.is_null() {
+                self.instance.destroy_surface_khr(self.surface, None);
+            }
+
+            // Destroyed last so it reports on everything else being destroyed.
+            if !self.messenger.is_null() {
+                self.instance.destroy_debug_utils_messenger_ext(self.messenger, None);
+            }
+
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/// A `Device` destroyed when dropped. Everything created from it holds on
+/// to it through an [`Owned`], so it outlives all of them, and it holds on
+/// to the instance it was created from in turn.
+#[derive(Debug)]
+pub struct OwnedDevice {
+    device: Device,
+    _instance: Arc<OwnedInstance>,
+}
+
+impl OwnedDevice {
+    pub fn new(device: Device, instance: Arc<OwnedInstance>) -> Self {
+        Self { device, _instance: instance }
+    }
+}
+
+impl Deref for OwnedDevice {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl Drop for OwnedDevice {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_device(None) };
+    }
+}
+
+/// Objects created from a device and destroyed through it.
+pub trait Destroy {
+    /// # Safety
+    ///
+    /// The device must no longer be using the objects, and they must not be
+    /// used or destroyed again afterwards.
+    unsafe fn destroy(&self, device: &Device);
+}
+
+macro_rules! impl_destroy {
+    ($($handle:ty => $destroy:ident),* $(,)?) => {
+        $(
+            impl Destroy for $handle {
+                unsafe fn destroy(&self, device: &Device) {
+                    device.$destroy(*self, None);
+                }
+            }
+        )*
+    };
+}
+
+impl_destroy! {
+    vk::Buffer => destroy_buffer,
+    vk::CommandPool => destroy_command_pool,
+    vk::DescriptorPool => destroy_descriptor_pool,
+    vk::DescriptorSetLayout => destroy_descriptor_set_layout,
+    vk::DeviceMemory => free_memory,
+    vk::Fence => destroy_fence,
+    vk::Framebuffer => destroy_framebuffer,
+    vk::Image => destroy_image,
+    vk::ImageView => destroy_image_view,
+    vk::PipelineCache => destroy_pipeline_cache,
+    vk::RenderPass => destroy_render_pass,
+    vk::Sampler => destroy_sampler,
+    vk::Semaphore => destroy_semaphore,
+    vk::SwapchainKHR => destroy_swapchain_khr,
+}
+
+impl<T: Destroy> Destroy for Vec<T> {
+    unsafe fn destroy(&self, device: &Device) {
+        self.iter().for_each(|o| o.destroy(device));
+    }
+}
+
+/// Owns `T`, destroying it through the device it was created from when
+/// dropped. The default owns nothing, so it can stand in for objects that
+/// were not created yet.
+#[derive(Debug, Default)]
+pub struct Owned<T: Destroy> {
+    value: T,
+    device: Option<Arc<OwnedDevice>>,
+}
+
+impl<T: Destroy> Owned<T> {
+    pub fn new(device: &Arc<OwnedDevice>, value: T) -> Self {
+        Self { value, device: Some(device.clone()) }
+    }
+
+    /// Gives up ownership of the value without destroying it, once it is
+    /// fully created and handed to whatever owns it from then on.
+    pub fn into_inner(mut self) -> T
+    where
+        T: Default,
+    {
+        self.device = None;
+        mem::take(&mut self.value)
+    }
+}
+
+impl<T: Destroy> Deref for Owned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Destroy> DerefMut for Owned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Destroy> Drop for Owned<T> {
+    fn drop(&mut self) {
+        if let Some(device) = &self.device {
+            unsafe { self.value.destroy(device) };
+        }
+    }
+}
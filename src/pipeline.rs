@@ -1,121 +1,457 @@
+use std::sync::Arc;
+
 use vulkanalia::{bytecode::Bytecode, vk::{self, DeviceV1_0, Handle, HasBuilder}, Device, Instance};
 
 use crate::appdata::AppData;
-use anyhow::Result;
-
-pub unsafe fn create_pipeline(device: &Device, data: &mut AppData) -> Result<()> {
-    let vert = include_bytes!("../compiled_shaders/vert.spv");
-    let frag = include_bytes!("../compiled_shaders/frag.spv");
-
-    let vert_shader_module = create_shader_module(device, &vert[..])?;
-    let frag_shader_module = create_shader_module(device, &frag[..])?;
-
-    let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
-        .stage(vk::ShaderStageFlags::VERTEX)
-        .module(vert_shader_module)
-        .name(b"main\0");
-
-    let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
-        .stage(vk::ShaderStageFlags::FRAGMENT)
-        .module(frag_shader_module)
-        .name(b"main\0");
-
-    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder();
-
-    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
-        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-        .primitive_restart_enable(false);
-
-    let viewport = vk::Viewport::builder()
-        .x(0.0)
-        .y(0.0)
-        .width(data.swapchain_extent.width as f32)
-        .height(data.swapchain_extent.height as f32)
-        .min_depth(0.0)
-        .max_depth(1.0);
-
-    let scissor = vk::Rect2D::builder()
-        .offset(vk::Offset2D { x: 0, y: 0 })
-        .extent(data.swapchain_extent);
-
-    let viewports = &[viewport];
-    let scissors = &[scissor];
-    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-        .viewports(viewports)
-        .scissors(scissors);
-
-    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
-        .depth_clamp_enable(false)
-        .rasterizer_discard_enable(false)
-        .polygon_mode(vk::PolygonMode::FILL)
-        .line_width(1.0)
-        .cull_mode(vk::CullModeFlags::BACK)
-        .front_face(vk::FrontFace::CLOCKWISE)
-        .depth_bias_enable(false);
-
-    let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
-        .sample_shading_enable(false)
-        .rasterization_samples(vk::SampleCountFlags::_1);
-
-    let attachment = vk::PipelineColorBlendAttachmentState::builder()
-        .color_write_mask(vk::ColorComponentFlags::all())
-        .blend_enable(false)
-        .src_color_blend_factor(vk::BlendFactor::ONE)  // Optional
-        .dst_color_blend_factor(vk::BlendFactor::ZERO) // Optional
-        .color_blend_op(vk::BlendOp::ADD)              // Optional
-        .src_alpha_blend_factor(vk::BlendFactor::ONE)  // Optional
-        .dst_alpha_blend_factor(vk::BlendFactor::ZERO) // Optional
-        .alpha_blend_op(vk::BlendOp::ADD);             // Optional
-    
-    
-    let attachments = &[attachment];
-    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
-        .logic_op_enable(false)
-        .logic_op(vk::LogicOp::COPY)
-        .attachments(attachments)
-        .blend_constants([0.0, 0.0, 0.0, 0.0]);
+use crate::depth::get_depth_format;
+use crate::owned::{Destroy, Owned, OwnedDevice};
+use crate::push_constants::{check_push_constants, MeshPushConstants};
+use crate::reflect::ShaderLayout;
+use crate::shaders::{load_shader, FRAGMENT_SHADER, VERTEX_SHADER};
+use crate::vertex::Vertex;
+use anyhow::{anyhow, Result};
 
-    let dynamic_states = &[
-        vk::DynamicState::VIEWPORT,
-        vk::DynamicState::LINE_WIDTH,
-    ];
-    
-    let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
-        .dynamic_states(dynamic_states);
-
-
-    let layout_info = vk::PipelineLayoutCreateInfo::builder();
-
-    data.pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
-
-    let stages = &[vert_stage, frag_stage];
-    let info = vk::GraphicsPipelineCreateInfo::builder()
-        .stages(stages)
-        .vertex_input_state(&vertex_input_state)
-        .input_assembly_state(&input_assembly_state)
-        .viewport_state(&viewport_state)
-        .rasterization_state(&rasterization_state)
-        .multisample_state(&multisample_state)
-        .color_blend_state(&color_blend_state)
-        .layout(data.pipeline_layout)
-        .render_pass(data.render_pass)
-        .base_pipeline_handle(vk::Pipeline::null()) // Optional.
-        .base_pipeline_index(-1)                    // Optional.    
-        .subpass(0);
-    
-    data.pipeline = device.create_graphics_pipelines(vk::PipelineCache::null(), &[info], None)?.0[0];
+/// How a pipeline combines its output with what is already in the color
+/// attachment.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrite the destination.
+    Opaque,
+    /// Blend with the destination using the source alpha.
+    Alpha,
+    /// Add the source, weighted by its alpha, to the destination.
+    Additive,
+}
+
+/// A graphics pipeline together with the layout it was created with.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GraphicsPipeline {
+    pub pipeline: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+}
+
+impl Destroy for GraphicsPipeline {
+    unsafe fn destroy(&self, device: &Device) {
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.layout, None);
+    }
+}
+
+/// Configures and creates a [`GraphicsPipeline`].
+///
+/// Everything but the shaders has a default matching our usual setup:
+/// filled, back-face culled triangle lists with counter-clockwise front
+/// faces, depth testing and opaque blending.
+#[derive(Clone, Debug)]
+pub struct GraphicsPipelineBuilder {
+    vertex_shader: Vec<u8>,
+    fragment_shader: Vec<u8>,
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    line_width: f32,
+    depth_test: bool,
+    depth_write: bool,
+    depth_compare_op: vk::CompareOp,
+    blend_mode: BlendMode,
+    samples: vk::SampleCountFlags,
+    sample_shading: bool,
+    dynamic_states: Vec<vk::DynamicState>,
+    extent: vk::Extent2D,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl Default for GraphicsPipelineBuilder {
+    fn default() -> Self {
+        Self {
+            vertex_shader: vec![],
+            fragment_shader: vec![],
+            vertex_bindings: vec![],
+            vertex_attributes: vec![],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: vk::CompareOp::LESS,
+            blend_mode: BlendMode::Opaque,
+            samples: vk::SampleCountFlags::_1,
+            sample_shading: false,
+            dynamic_states: vec![],
+            extent: vk::Extent2D::default(),
+            set_layouts: vec![],
+            push_constant_ranges: vec![],
+            pipeline_cache: vk::PipelineCache::null(),
+        }
+    }
+}
+
+impl GraphicsPipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the SPIR-V of the vertex and fragment stages. Both use `main`
+    /// as their entry point.
+    pub fn shaders(mut self, vertex: &[u8], fragment: &[u8]) -> Self {
+        self.vertex_shader = vertex.to_vec();
+        self.fragment_shader = fragment.to_vec();
+        self
+    }
+
+    /// Sets the vertex buffers and the attributes read from them. Without
+    /// any, they are derived from the shaders: a single buffer at binding 0
+    /// holding the vertex shader's inputs tightly packed in location order.
+    pub fn vertex_layout(
+        mut self,
+        bindings: &[vk::VertexInputBindingDescription],
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Self {
+        self.vertex_bindings = bindings.to_vec();
+        self.vertex_attributes = attributes.to_vec();
+        self
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn rasterization(
+        mut self,
+        polygon_mode: vk::PolygonMode,
+        cull_mode: vk::CullModeFlags,
+        front_face: vk::FrontFace,
+    ) -> Self {
+        self.polygon_mode = polygon_mode;
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn depth(mut self, test: bool, write: bool, compare_op: vk::CompareOp) -> Self {
+        self.depth_test = test;
+        self.depth_write = write;
+        self.depth_compare_op = compare_op;
+        self
+    }
+
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn multisampling(mut self, samples: vk::SampleCountFlags, sample_shading: bool) -> Self {
+        self.samples = samples;
+        self.sample_shading = sample_shading;
+        self
+    }
+
+    pub fn dynamic_states(mut self, dynamic_states: &[vk::DynamicState]) -> Self {
+        self.dynamic_states = dynamic_states.to_vec();
+        self
+    }
+
+    /// Sets the viewport and scissor used unless they are dynamic. Building
+    /// fails without one if either of them is not.
+    pub fn extent(mut self, extent: vk::Extent2D) -> Self {
+        self.extent = extent;
+        self
+    }
+
+    pub fn set_layouts(mut self, set_layouts: &[vk::DescriptorSetLayout]) -> Self {
+        self.set_layouts = set_layouts.to_vec();
+        self
+    }
+
+    /// Adds a push constant range. Without any, the range is derived from
+    /// the shaders.
+    pub fn push_constant_range(mut self, range: vk::PushConstantRange) -> Self {
+        self.push_constant_ranges.push(range);
+        self
+    }
+
+    /// Sets the cache pipeline creation reuses and stores results in.
+    pub fn pipeline_cache(mut self, pipeline_cache: vk::PipelineCache) -> Self {
+        self.pipeline_cache = pipeline_cache;
+        self
+    }
+
+    /// Creates the pipeline layout and a pipeline for `subpass` of `render_pass`.
+    ///
+    /// The shaders are reflected first to derive the vertex input state and
+    /// push constant ranges that were not set, and to report the ones that
+    /// were set but do not match what the shaders declare as errors.
+    pub unsafe fn build(
+        &self,
+        device: &Device,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+    ) -> Result<GraphicsPipeline> {
+        let dynamic_viewport = self.dynamic_states.contains(&vk::DynamicState::VIEWPORT)
+            && self.dynamic_states.contains(&vk::DynamicState::SCISSOR);
+        if !dynamic_viewport && (self.extent.width == 0 || self.extent.height == 0) {
+            return Err(anyhow!(
+                "A pipeline needs an extent unless its viewport and scissor are dynamic, got {:?}.",
+                self.extent,
+            ));
+        }
+
+        let reflected = ShaderLayout::reflect(&[&self.vertex_shader, &self.fragment_shader])?;
+
+        let (vertex_bindings, vertex_attributes) = if self.vertex_attributes.is_empty() {
+            let (binding, attributes) = reflected.get_vertex_input(0)?;
+            let bindings = if attributes.is_empty() { vec![] } else { vec![binding] };
+            (bindings, attributes)
+        } else {
+            reflected.check_vertex_input(&self.vertex_attributes)?;
+            (self.vertex_bindings.clone(), self.vertex_attributes.clone())
+        };
+
+        let push_constant_ranges = if self.push_constant_ranges.is_empty() {
+            reflected.push_constants.into_iter().collect()
+        } else {
+            reflected.check_push_constants(&self.push_constant_ranges)?;
+            self.push_constant_ranges.clone()
+        };
+
+        let set_layouts = &self.set_layouts;
+        let layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+
+        let layout = device.create_pipeline_layout(&layout_info, None)?;
+
+        match self.build_pipeline(device, layout, &vertex_bindings, &vertex_attributes, render_pass, subpass) {
+            Ok(pipeline) => Ok(GraphicsPipeline { pipeline, layout }),
+            Err(e) => {
+                device.destroy_pipeline_layout(layout, None);
+                Err(e)
+            }
+        }
+    }
+
+    unsafe fn build_pipeline(
+        &self,
+        device: &Device,
+        layout: vk::PipelineLayout,
+        vertex_bindings: &[vk::VertexInputBindingDescription],
+        vertex_attributes: &[vk::VertexInputAttributeDescription],
+        render_pass: vk::RenderPass,
+        subpass: u32,
+    ) -> Result<vk::Pipeline> {
+        let vert_shader_module = create_shader_module(device, &self.vertex_shader)?;
+        let frag_shader_module = match create_shader_module(device, &self.fragment_shader) {
+            Ok(module) => module,
+            Err(e) => {
+                device.destroy_shader_module(vert_shader_module, None);
+                return Err(e);
+            }
+        };
+
+        let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vert_shader_module)
+            .name(b"main\0");
+
+        let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(frag_shader_module)
+            .name(b"main\0");
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(vertex_bindings)
+            .vertex_attribute_descriptions(vertex_attributes);
 
-    device.destroy_shader_module(vert_shader_module, None);
-    device.destroy_shader_module(frag_shader_module, None);
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(self.topology)
+            .primitive_restart_enable(false);
 
+        let viewport = vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(self.extent.width as f32)
+            .height(self.extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+
+        let scissor = vk::Rect2D::builder()
+            .offset(vk::Offset2D { x: 0, y: 0 })
+            .extent(self.extent);
+
+        // Dynamic viewports and scissors are set while recording, so only
+        // their count is part of the pipeline.
+        let viewports = &[viewport];
+        let scissors = &[scissor];
+        let mut viewport_state = vk::PipelineViewportStateCreateInfo::builder();
+        viewport_state = if self.dynamic_states.contains(&vk::DynamicState::VIEWPORT) {
+            viewport_state.viewport_count(1)
+        } else {
+            viewport_state.viewports(viewports)
+        };
+        viewport_state = if self.dynamic_states.contains(&vk::DynamicState::SCISSOR) {
+            viewport_state.scissor_count(1)
+        } else {
+            viewport_state.scissors(scissors)
+        };
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(self.polygon_mode)
+            .line_width(self.line_width)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .depth_bias_enable(false);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .sample_shading_enable(self.sample_shading)
+            .min_sample_shading(0.2) // Closer to 1 is smoother.
+            .rasterization_samples(self.samples);
+
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(self.depth_test)
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare_op)
+            .depth_bounds_test_enable(false)
+            .min_depth_bounds(0.0) // Optional.
+            .max_depth_bounds(1.0) // Optional.
+            .stencil_test_enable(false);
+
+        let attachment = match self.blend_mode {
+            BlendMode::Opaque => vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::all())
+                .blend_enable(false)
+                .src_color_blend_factor(vk::BlendFactor::ONE)  // Optional
+                .dst_color_blend_factor(vk::BlendFactor::ZERO) // Optional
+                .color_blend_op(vk::BlendOp::ADD)              // Optional
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)  // Optional
+                .dst_alpha_blend_factor(vk::BlendFactor::ZERO) // Optional
+                .alpha_blend_op(vk::BlendOp::ADD),             // Optional
+            BlendMode::Alpha => vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::all())
+                .blend_enable(true)
+                .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+                .alpha_blend_op(vk::BlendOp::ADD),
+            BlendMode::Additive => vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::all())
+                .blend_enable(true)
+                .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(vk::BlendFactor::ONE)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+                .alpha_blend_op(vk::BlendOp::ADD),
+        };
+
+        let attachments = &[attachment];
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .attachments(attachments)
+            .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&self.dynamic_states);
+
+        let stages = &[vert_stage, frag_stage];
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(layout)
+            .render_pass(render_pass)
+            .base_pipeline_handle(vk::Pipeline::null()) // Optional.
+            .base_pipeline_index(-1)                    // Optional.
+            .subpass(subpass);
+
+        let result = device.create_graphics_pipelines(self.pipeline_cache, &[info], None);
+
+        device.destroy_shader_module(vert_shader_module, None);
+        device.destroy_shader_module(frag_shader_module, None);
+
+        Ok(result?.0[0])
+    }
+}
+
+/// The index into `AppData::pipelines` of the wireframe pipeline, which only
+/// exists if the device supports non-solid fill modes.
+pub const WIREFRAME_PIPELINE: usize = 1;
+
+/// Creates the pipelines our meshes are drawn with. The first one is the
+/// default; the second draws wireframes if the device supports it.
+///
+/// The viewport and scissor are dynamic, so the pipelines only need to be
+/// rebuilt when the render pass changes, not when the extent does.
+pub unsafe fn create_pipelines(instance: &Instance, device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
+    data.pipelines = build_pipelines(instance, device, data)?;
     Ok(())
 }
 
+/// Builds the pipelines stored by `create_pipelines` without replacing the
+/// current ones, so a failed rebuild leaves them usable.
+pub unsafe fn build_pipelines(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &AppData,
+) -> Result<Owned<Vec<GraphicsPipeline>>> {
+    let dir = data.shader_dir.as_deref();
+    let vert = load_shader(dir, VERTEX_SHADER)?;
+    let frag = load_shader(dir, FRAGMENT_SHADER)?;
+
+    // The vertex input state and push constant range are derived from the
+    // shaders, so they have to agree with the vertices and constants we feed
+    // them.
+    Vertex::check_shader_layout(&data.shader_layout)?;
+    check_push_constants::<MeshPushConstants>(instance, data)?;
+
+    let builder = GraphicsPipelineBuilder::new()
+        .shaders(&vert, &frag)
+        .multisampling(data.msaa_samples, data.sample_shading)
+        .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+        .set_layouts(&[*data.descriptor_set_layout, *data.texture_set_layout])
+        .pipeline_cache(*data.pipeline_cache);
+
+    // Dropping the pipelines built so far destroys them if a later one fails.
+    let mut pipelines = Owned::new(device, vec![builder.build(device, *data.render_pass, 0)?]);
+
+    if data.enabled_features.fill_mode_non_solid == vk::TRUE {
+        let wireframe = builder
+            .rasterization(vk::PolygonMode::LINE, vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE)
+            .build(device, *data.render_pass, 0)?;
+        pipelines.push(wireframe);
+    }
+
+    Ok(pipelines)
+}
+
 unsafe fn create_shader_module(
     device: &Device,
     bytecode: &[u8],
 ) -> Result<vk::ShaderModule> {
-    let bytecode = Bytecode::new(bytecode).unwrap();
+    let bytecode = Bytecode::new(bytecode).map_err(|e| anyhow!("Invalid SPIR-V: {}.", e))?;
 
     let info = vk::ShaderModuleCreateInfo::builder()
         .code_size(bytecode.code_size())
@@ -126,34 +462,116 @@ unsafe fn create_shader_module(
 
 pub unsafe fn create_render_pass(
     instance: &Instance,
-    device: &Device,
+    device: &Arc<OwnedDevice>,
     data: &mut AppData,
 ) -> Result<()> {
+    let multisampled = data.msaa_samples != vk::SampleCountFlags::_1;
+    let presented_layout = if data.headless {
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+    } else {
+        vk::ImageLayout::PRESENT_SRC_KHR
+    };
+
+    // With multisampling we render into a separate image that is resolved
+    // into the swapchain image, otherwise into the swapchain image itself.
     let color_attachment = vk::AttachmentDescription::builder()
         .format(data.swapchain_format)
-        .samples(vk::SampleCountFlags::_1)
+        .samples(data.msaa_samples)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(if multisampled {
+            vk::AttachmentStoreOp::DONT_CARE
+        } else {
+            vk::AttachmentStoreOp::STORE
+        })
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(if multisampled {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            presented_layout
+        });
+
+    let depth_stencil_attachment = vk::AttachmentDescription::builder()
+        .format(get_depth_format(instance, data)?)
+        .samples(data.msaa_samples)
         .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+    let color_resolve_attachment = vk::AttachmentDescription::builder()
+        .format(data.swapchain_format)
+        .samples(vk::SampleCountFlags::_1)
+        .load_op(vk::AttachmentLoadOp::DONT_CARE)
         .store_op(vk::AttachmentStoreOp::STORE)
         .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
         .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
         .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        .final_layout(presented_layout);
 
     let color_attachment_ref = vk::AttachmentReference::builder()
         .attachment(0)
         .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+    let depth_stencil_attachment_ref = vk::AttachmentReference::builder()
+        .attachment(1)
+        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+    let color_resolve_attachment_ref = vk::AttachmentReference::builder()
+        .attachment(2)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
     let color_attachments = &[color_attachment_ref];
-    let subpass = vk::SubpassDescription::builder()
+    let resolve_attachments = &[color_resolve_attachment_ref];
+    let mut subpass = vk::SubpassDescription::builder()
     .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-    .color_attachments(color_attachments);
+    .color_attachments(color_attachments)
+    .depth_stencil_attachment(&depth_stencil_attachment_ref);
+    if multisampled {
+        subpass = subpass.resolve_attachments(resolve_attachments);
+    }
+
+    // The depth and multisampled color images are shared by all frames in
+    // flight, so the previous frame's writes have to finish before this
+    // frame clears them.
+    let dependency = vk::SubpassDependency::builder()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+        .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+    // In headless mode the image is copied out right after the render pass.
+    let readback_dependency = vk::SubpassDependency::builder()
+        .src_subpass(0)
+        .dst_subpass(vk::SUBPASS_EXTERNAL)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
 
-    let attachments = &[color_attachment];
+    let attachments: &[_] = if multisampled {
+        &[color_attachment, depth_stencil_attachment, color_resolve_attachment]
+    } else {
+        &[color_attachment, depth_stencil_attachment]
+    };
     let subpasses = &[subpass];
+    let dependencies: &[_] = if data.headless {
+        &[dependency, readback_dependency]
+    } else {
+        &[dependency]
+    };
     let info = vk::RenderPassCreateInfo::builder()
         .attachments(attachments)
-        .subpasses(subpasses);
+        .subpasses(subpasses)
+        .dependencies(dependencies);
     
-    data.render_pass = device.create_render_pass(&info, None)?;
+    data.render_pass = Owned::new(device, device.create_render_pass(&info, None)?);
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use vulkanalia::vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+use crate::owned::{Owned, OwnedDevice};
+
+/// The size of the header every pipeline cache starts with.
+const HEADER_SIZE: usize = 32;
+
+/// Creates `data.pipeline_cache`, seeded from the file at `path` if it holds
+/// a cache written for our physical device. Anything else is discarded.
+pub unsafe fn create_pipeline_cache(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &mut AppData,
+    path: Option<&Path>,
+) -> Result<()> {
+    let initial_data = match path {
+        Some(path) => load_pipeline_cache(instance, data, path),
+        None => vec![],
+    };
+
+    let info = vk::PipelineCacheCreateInfo::builder()
+        .initial_data(&initial_data);
+
+    let pipeline_cache = match device.create_pipeline_cache(&info, None) {
+        Ok(cache) => cache,
+        // The header looked right, but the driver still rejected the data.
+        Err(e) if !initial_data.is_empty() => {
+            warn!("Discarding pipeline cache rejected by the driver: {}", e);
+            let info = vk::PipelineCacheCreateInfo::builder();
+            device.create_pipeline_cache(&info, None)?
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    data.pipeline_cache = Owned::new(device, pipeline_cache);
+
+    Ok(())
+}
+
+/// Reads the pipeline cache at `path`, or returns nothing if it does not
+/// exist or was not written for our physical device.
+unsafe fn load_pipeline_cache(instance: &Instance, data: &AppData, path: &Path) -> Vec<u8> {
+    let Ok(bytes) = fs::read(path) else {
+        return vec![];
+    };
+
+    match check_header(instance, data, &bytes) {
+        Ok(()) => {
+            info!("Loaded pipeline cache `{}` ({} bytes).", path.display(), bytes.len());
+            bytes
+        }
+        Err(e) => {
+            warn!("Discarding pipeline cache `{}`: {}", path.display(), e);
+            vec![]
+        }
+    }
+}
+
+/// Writes the contents of `data.pipeline_cache` to `path`.
+pub unsafe fn save_pipeline_cache(device: &Device, data: &AppData, path: &Path) -> Result<()> {
+    let bytes = device.get_pipeline_cache_data(*data.pipeline_cache)?;
+
+    // Write to a temporary file first so a crash never leaves a torn cache.
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, &bytes)?;
+    fs::rename(&temp, path)?;
+
+    info!("Saved pipeline cache `{}` ({} bytes).", path.display(), bytes.len());
+
+    Ok(())
+}
+
+/// Checks that `bytes` start with a pipeline cache header matching our
+/// physical device's vendor, device and pipeline cache UUID.
+unsafe fn check_header(instance: &Instance, data: &AppData, bytes: &[u8]) -> Result<()> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(anyhow!("It is only {} bytes long.", bytes.len()));
+    }
+
+    let word = |i: usize| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+    let (length, version, vendor_id, device_id) = (word(0), word(1), word(2), word(3));
+    let uuid = &bytes[16..32];
+
+    let properties = instance.get_physical_device_properties(data.physical_device);
+
+    if (length as usize) < HEADER_SIZE || length as usize > bytes.len() {
+        Err(anyhow!("Its header length {} is invalid.", length))
+    } else if version != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32 {
+        Err(anyhow!("Its header version {} is unsupported.", version))
+    } else if vendor_id != properties.vendor_id || device_id != properties.device_id {
+        Err(anyhow!(
+            "It was written for device {:04x}:{:04x}, not {:04x}:{:04x}.",
+            vendor_id,
+            device_id,
+            properties.vendor_id,
+            properties.device_id,
+        ))
+    } else if uuid != &properties.pipeline_cache_uuid.0[..] {
+        Err(anyhow!("It was written by a different driver."))
+    } else {
+        Ok(())
+    }
+}
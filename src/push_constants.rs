@@ -0,0 +1,95 @@
+use std::mem::size_of;
+use std::slice;
+
+use anyhow::{anyhow, Result};
+use cgmath::SquareMatrix;
+use vulkanalia::vk::{self, DeviceV1_0, InstanceV1_0};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+use crate::uniforms::Mat4;
+
+/// A typed block of data pushed to a pipeline's shaders with
+/// `cmd_push_constants`, occupying the start of its push constant range.
+///
+/// # Safety
+///
+/// `as_bytes` reads every byte of the value, so implementors must be
+/// `#[repr(C)]` and have no padding.
+pub unsafe trait PushConstants: Copy {
+    /// The shader stages that read the block.
+    const STAGES: vk::ShaderStageFlags;
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast::<u8>(), size_of::<Self>()) }
+    }
+}
+
+/// The per-draw data of a mesh, so meshes can differ without touching
+/// descriptor sets.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MeshPushConstants {
+    /// The mesh's transform within the model.
+    pub transform: Mat4,
+    /// The index of the mesh's material, or `u32::MAX` for none.
+    pub material_id: u32,
+}
+
+impl Default for MeshPushConstants {
+    fn default() -> Self {
+        Self { transform: Mat4::identity(), material_id: u32::MAX }
+    }
+}
+
+// SAFETY: `#[repr(C)]`, and a `Mat4` is followed by a `u32` without padding.
+unsafe impl PushConstants for MeshPushConstants {
+    /// Only the vertex shader declares the block.
+    const STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::VERTEX;
+}
+
+/// Checks that the shaders declare `T` as their push constant block, read
+/// by the same stages, and that the picked device can hold it.
+pub unsafe fn check_push_constants<T: PushConstants>(instance: &Instance, data: &AppData) -> Result<()> {
+    let name = std::any::type_name::<T>();
+    let range = data.shader_layout.push_constants
+        .ok_or_else(|| anyhow!("Push constant block `{}` is pushed, but the shaders declare none.", name))?;
+
+    let size = size_of::<T>() as u32;
+    if range.offset != 0 || range.size != size || range.stage_flags != T::STAGES {
+        return Err(anyhow!(
+            "The shaders declare {} bytes of push constants in {:?}, but `{}` is {} bytes pushed to {:?}.",
+            range.size,
+            range.stage_flags,
+            name,
+            size,
+            T::STAGES,
+        ));
+    }
+
+    let max_size = instance
+        .get_physical_device_properties(data.physical_device)
+        .limits
+        .max_push_constants_size;
+
+    if size > max_size {
+        return Err(anyhow!(
+            "Push constant block `{}` is {} bytes, but the device supports at most {}.",
+            name,
+            size,
+            max_size,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Records pushing `constants` for the following draws using `layout`.
+pub unsafe fn push_constants<T: PushConstants>(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    layout: vk::PipelineLayout,
+    constants: &T,
+) {
+    device.cmd_push_constants(command_buffer, layout, T::STAGES, 0, constants.as_bytes());
+}
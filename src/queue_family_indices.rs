@@ -28,6 +28,15 @@ impl QueueFamilyIndices {
             .position(|p| p.queue_flags.contains(vk::QueueFlags::GRAPHICS))
             .map(|i| i as u32);
 
+        // Without a surface there is nothing to present to, so presentation
+        // support is irrelevant.
+        if data.headless {
+            return match graphics {
+                Some(graphics) => Ok(Self { graphics, present: graphics }),
+                None => Err(anyhow!(SuitabilityError("Missing required queue families."))),
+            };
+        }
+
         let mut present = None;
         for (index, properties) in properties.iter().enumerate() {
             if instance.get_physical_device_surface_support_khr(
@@ -0,0 +1,836 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::vk::{self, HasBuilder};
+
+const MAGIC: u32 = 0x0723_0203;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_OUTPUT: u32 = 3;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// How deeply types may nest, so a cyclic type fails instead of recursing
+/// forever.
+const MAX_TYPE_DEPTH: u32 = 64;
+
+/// A resource a shader accesses through a descriptor set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+}
+
+/// What a single shader module declares, as far as pipeline creation cares.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderInfo {
+    pub stage: vk::ShaderStageFlags,
+    pub bindings: Vec<DescriptorBinding>,
+    /// The size in bytes of the push constant block, if there is one.
+    pub push_constant_size: Option<u32>,
+    /// The user-defined inputs by location.
+    pub inputs: Vec<(u32, vk::Format)>,
+    /// The user-defined outputs by location.
+    pub outputs: Vec<(u32, vk::Format)>,
+}
+
+/// The interface of the shader stages of a pipeline, merged and checked for
+/// consistency.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderLayout {
+    /// The descriptor bindings of every stage, sorted by set and binding.
+    pub bindings: Vec<DescriptorBinding>,
+    pub push_constants: Option<vk::PushConstantRange>,
+    /// The inputs of the vertex stage by location.
+    pub vertex_inputs: Vec<(u32, vk::Format)>,
+}
+
+impl ShaderLayout {
+    /// Reflects and merges the SPIR-V of each stage of a pipeline, ordered
+    /// from the first stage to the last.
+    pub fn reflect(stages: &[&[u8]]) -> Result<Self> {
+        let stages = stages.iter().map(|s| reflect(s)).collect::<Result<Vec<_>>>()?;
+        Self::new(&stages)
+    }
+
+    pub fn new(stages: &[ShaderInfo]) -> Result<Self> {
+        let mut layout = Self::default();
+
+        for stage in stages {
+            for binding in &stage.bindings {
+                let existing = layout.bindings
+                    .iter_mut()
+                    .find(|b| b.set == binding.set && b.binding == binding.binding);
+                match existing {
+                    Some(existing) if existing.descriptor_type != binding.descriptor_type || existing.count != binding.count => {
+                        return Err(anyhow!(
+                            "Set {} binding {} is a {:?} x{} in {:?} but a {:?} x{} in {:?}.",
+                            binding.set,
+                            binding.binding,
+                            existing.descriptor_type,
+                            existing.count,
+                            existing.stages,
+                            binding.descriptor_type,
+                            binding.count,
+                            binding.stages,
+                        ));
+                    }
+                    Some(existing) => existing.stages |= binding.stages,
+                    None => layout.bindings.push(*binding),
+                }
+            }
+
+            if let Some(size) = stage.push_constant_size {
+                let range = layout.push_constants.get_or_insert_with(Default::default);
+                range.stage_flags |= stage.stage;
+                range.size = range.size.max(size);
+            }
+
+            if stage.stage == vk::ShaderStageFlags::VERTEX {
+                layout.vertex_inputs = stage.inputs.clone();
+            }
+        }
+
+        for (previous, next) in stages.iter().zip(stages.iter().skip(1)) {
+            for (location, format) in &next.inputs {
+                match previous.outputs.iter().find(|(l, _)| l == location) {
+                    Some((_, output)) if output == format => {}
+                    Some((_, output)) => return Err(anyhow!(
+                        "Location {} is a {:?} output of the {:?} stage but a {:?} input of the {:?} stage.",
+                        location, output, previous.stage, format, next.stage,
+                    )),
+                    None => return Err(anyhow!(
+                        "The {:?} stage reads location {}, which the {:?} stage does not write.",
+                        next.stage, location, previous.stage,
+                    )),
+                }
+            }
+        }
+
+        layout.bindings.sort_by_key(|b| (b.set, b.binding));
+
+        Ok(layout)
+    }
+
+    /// Returns the bindings of a descriptor set layout for `set`.
+    pub fn get_set_layout_bindings(&self, set: u32) -> Vec<vk::DescriptorSetLayoutBinding> {
+        self.bindings
+            .iter()
+            .filter(|b| b.set == set)
+            .map(|b| vk::DescriptorSetLayoutBinding {
+                binding: b.binding,
+                descriptor_type: b.descriptor_type,
+                descriptor_count: b.count,
+                stage_flags: b.stages,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Checks that `set` has exactly the single `(binding, type)` descriptors
+    /// we write into it.
+    pub fn check_set(&self, set: u32, expected: &[(u32, vk::DescriptorType)]) -> Result<()> {
+        let actual = self.bindings
+            .iter()
+            .filter(|b| b.set == set)
+            .map(|b| (b.binding, b.descriptor_type, b.count))
+            .collect::<Vec<_>>();
+
+        let expected = expected.iter().map(|(b, t)| (*b, *t, 1)).collect::<Vec<_>>();
+        if actual != expected {
+            return Err(anyhow!(
+                "The shaders declare set {} as {:?}, but we provide {:?} (binding, type, count).",
+                set, actual, expected,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that descriptor sets laid out for `other` can be bound for
+    /// these shaders.
+    pub fn check_sets_compatible(&self, other: &ShaderLayout) -> Result<()> {
+        let compatible = self.bindings.len() == other.bindings.len()
+            && self.bindings.iter().zip(&other.bindings).all(|(a, b)| {
+                (a.set, a.binding, a.descriptor_type, a.count) == (b.set, b.binding, b.descriptor_type, b.count)
+                    && b.stages.contains(a.stages)
+            });
+
+        if !compatible {
+            return Err(anyhow!(
+                "The shaders' descriptor sets {:?} no longer match the ones in use {:?}.",
+                self.bindings,
+                other.bindings,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that pipelines for these shaders fit everything created for the
+    /// shaders `other` was reflected from: the descriptor sets, the push
+    /// constant range and the vertex attributes.
+    pub fn check_unchanged(&self, other: &ShaderLayout) -> Result<()> {
+        self.check_sets_compatible(other)?;
+
+        if self.push_constants != other.push_constants {
+            return Err(anyhow!(
+                "The shaders' push constants {:?} no longer match the ones in use {:?}.",
+                self.push_constants,
+                other.push_constants,
+            ));
+        }
+
+        if self.vertex_inputs != other.vertex_inputs {
+            return Err(anyhow!(
+                "The shaders' vertex inputs {:?} no longer match the ones in use {:?}.",
+                self.vertex_inputs,
+                other.vertex_inputs,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `attributes` provide every vertex input in the format the
+    /// vertex shader reads it in.
+    pub fn check_vertex_input(&self, attributes: &[vk::VertexInputAttributeDescription]) -> Result<()> {
+        for (location, format) in &self.vertex_inputs {
+            match attributes.iter().find(|a| a.location == *location) {
+                Some(a) if a.format == *format => {}
+                Some(a) => return Err(anyhow!(
+                    "The vertex shader reads location {} as {:?}, but the vertex attribute is {:?}.",
+                    location, format, a.format,
+                )),
+                None => return Err(anyhow!(
+                    "The vertex shader reads location {}, which no vertex attribute provides.",
+                    location,
+                )),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describes a vertex buffer at `binding` holding the vertex shader's
+    /// inputs, tightly packed in location order, one vertex after another.
+    pub fn get_vertex_input(
+        &self,
+        binding: u32,
+    ) -> Result<(vk::VertexInputBindingDescription, Vec<vk::VertexInputAttributeDescription>)> {
+        let mut inputs = self.vertex_inputs.clone();
+        inputs.sort_by_key(|(location, _)| *location);
+
+        let mut offset = 0u32;
+        let mut attributes = vec![];
+        for (location, format) in inputs {
+            attributes.push(
+                vk::VertexInputAttributeDescription::builder()
+                    .binding(binding)
+                    .location(location)
+                    .format(format)
+                    .offset(offset)
+                    .build(),
+            );
+
+            offset = offset
+                .checked_add(get_format_size(format)?)
+                .ok_or_else(|| anyhow!("The vertex inputs overflow."))?;
+        }
+
+        let binding = vk::VertexInputBindingDescription::builder()
+            .binding(binding)
+            .stride(offset)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .build();
+
+        Ok((binding, attributes))
+    }
+
+    /// Checks that `ranges` cover the push constants of every stage.
+    pub fn check_push_constants(&self, ranges: &[vk::PushConstantRange]) -> Result<()> {
+        let Some(required) = self.push_constants else {
+            return Ok(());
+        };
+
+        let covered = ranges.iter().any(|r| {
+            r.offset == 0 && r.size >= required.size && r.stage_flags.contains(required.stage_flags)
+        });
+
+        if !covered {
+            return Err(anyhow!(
+                "The shaders use {} bytes of push constants in {:?}, but the pipeline layout provides {:?}.",
+                required.size, required.stage_flags, ranges,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { storage_class: u32, pointee: u32 },
+}
+
+/// The parts of a SPIR-V module we need to reflect it.
+#[derive(Default)]
+struct Module {
+    execution_model: Option<u32>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<(u32, u32), u32>,
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+    /// `(result type, id, storage class)` of each global variable.
+    variables: Vec<(u32, u32, u32)>,
+}
+
+/// Reflects the SPIR-V `bytecode` of a single-entry-point shader module.
+pub fn reflect(bytecode: &[u8]) -> Result<ShaderInfo> {
+    let module = parse(bytecode)?;
+
+    let stage = match module.execution_model {
+        Some(0) => vk::ShaderStageFlags::VERTEX,
+        Some(1) => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        Some(2) => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        Some(3) => vk::ShaderStageFlags::GEOMETRY,
+        Some(4) => vk::ShaderStageFlags::FRAGMENT,
+        Some(5) => vk::ShaderStageFlags::COMPUTE,
+        Some(model) => return Err(anyhow!("Unsupported execution model {}.", model)),
+        None => return Err(anyhow!("The shader has no entry point.")),
+    };
+
+    let mut info = ShaderInfo { stage, ..Default::default() };
+
+    for (type_id, id, storage_class) in &module.variables {
+        let Some(Type::Pointer { pointee, .. }) = module.types.get(type_id) else {
+            return Err(anyhow!("Variable %{} is not a pointer.", id));
+        };
+
+        match *storage_class {
+            STORAGE_CLASS_UNIFORM_CONSTANT | STORAGE_CLASS_UNIFORM | STORAGE_CLASS_STORAGE_BUFFER => {
+                let (Some(set), Some(binding)) = (
+                    module.decorations.get(&(*id, DECORATION_DESCRIPTOR_SET)),
+                    module.decorations.get(&(*id, DECORATION_BINDING)),
+                ) else {
+                    continue;
+                };
+
+                let (descriptor_type, count) = module.get_descriptor_type(*pointee, *storage_class, 0)?;
+                info.bindings.push(DescriptorBinding {
+                    set: *set,
+                    binding: *binding,
+                    descriptor_type,
+                    count,
+                    stages: stage,
+                });
+            }
+            STORAGE_CLASS_PUSH_CONSTANT => {
+                info.push_constant_size = Some(module.get_size(*pointee, None, 0)?);
+            }
+            STORAGE_CLASS_INPUT | STORAGE_CLASS_OUTPUT => {
+                // Built-ins such as `gl_Position` have no location.
+                let Some(location) = module.decorations.get(&(*id, DECORATION_LOCATION)) else {
+                    continue;
+                };
+
+                let format = module.get_format(*pointee)?;
+                if *storage_class == STORAGE_CLASS_INPUT {
+                    info.inputs.push((*location, format));
+                } else {
+                    info.outputs.push((*location, format));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    info.inputs.sort_by_key(|(l, _)| *l);
+    info.outputs.sort_by_key(|(l, _)| *l);
+
+    Ok(info)
+}
+
+fn parse(bytecode: &[u8]) -> Result<Module> {
+    if !bytecode.len().is_multiple_of(4) || bytecode.len() < 20 {
+        return Err(anyhow!("SPIR-V must be a whole number of words, got {} bytes.", bytecode.len()));
+    }
+
+    let mut words = bytecode
+        .chunks_exact(4)
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+        .collect::<Vec<_>>();
+
+    if words[0] == MAGIC.swap_bytes() {
+        words.iter_mut().for_each(|w| *w = w.swap_bytes());
+    } else if words[0] != MAGIC {
+        return Err(anyhow!("Invalid SPIR-V magic number {:#010x}.", words[0]));
+    }
+
+    let mut module = Module::default();
+    let mut offset = 5;
+    while offset < words.len() {
+        let count = (words[offset] >> 16) as usize;
+        let opcode = words[offset] & 0xFFFF;
+        if count == 0 || offset + count > words.len() {
+            return Err(anyhow!("Truncated SPIR-V instruction at word {}.", offset));
+        }
+
+        let operands = &words[offset + 1..offset + count];
+        let operand = |i: usize| {
+            operands
+                .get(i)
+                .copied()
+                .ok_or_else(|| anyhow!("Missing operand {} of opcode {}.", i, opcode))
+        };
+
+        match opcode {
+            OP_ENTRY_POINT => {
+                if module.execution_model.is_some() {
+                    return Err(anyhow!("Shaders with several entry points are not supported."));
+                }
+                module.execution_model = Some(operand(0)?);
+            }
+            OP_TYPE_BOOL => {
+                module.types.insert(operand(0)?, Type::Bool);
+            }
+            OP_TYPE_INT => {
+                let ty = Type::Int { width: operand(1)?, signed: operand(2)? == 1 };
+                module.types.insert(operand(0)?, ty);
+            }
+            OP_TYPE_FLOAT => {
+                module.types.insert(operand(0)?, Type::Float { width: operand(1)? });
+            }
+            OP_TYPE_VECTOR => {
+                let ty = Type::Vector { component: operand(1)?, count: operand(2)? };
+                module.types.insert(operand(0)?, ty);
+            }
+            OP_TYPE_MATRIX => {
+                let ty = Type::Matrix { column: operand(1)?, count: operand(2)? };
+                module.types.insert(operand(0)?, ty);
+            }
+            OP_TYPE_IMAGE => {
+                let ty = Type::Image { dim: operand(2)?, sampled: operand(6)? };
+                module.types.insert(operand(0)?, ty);
+            }
+            OP_TYPE_SAMPLER => {
+                module.types.insert(operand(0)?, Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                module.types.insert(operand(0)?, Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                let ty = Type::Array { element: operand(1)?, length: operand(2)? };
+                module.types.insert(operand(0)?, ty);
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                module.types.insert(operand(0)?, Type::RuntimeArray { element: operand(1)? });
+            }
+            OP_TYPE_STRUCT => {
+                let members = operands
+                    .get(1..)
+                    .ok_or_else(|| anyhow!("Missing operand 0 of opcode {}.", opcode))?;
+                let ty = Type::Struct { members: members.to_vec() };
+                module.types.insert(operand(0)?, ty);
+            }
+            OP_TYPE_POINTER => {
+                let ty = Type::Pointer { storage_class: operand(1)?, pointee: operand(2)? };
+                module.types.insert(operand(0)?, ty);
+            }
+            OP_CONSTANT => {
+                // Only the low word matters for the sizes and lengths we read.
+                module.constants.insert(operand(1)?, operand(2)?);
+            }
+            OP_VARIABLE => {
+                module.variables.push((operand(0)?, operand(1)?, operand(2)?));
+            }
+            OP_DECORATE => {
+                let value = operands.get(2).copied().unwrap_or(0);
+                module.decorations.insert((operand(0)?, operand(1)?), value);
+            }
+            OP_MEMBER_DECORATE => {
+                let value = operands.get(3).copied().unwrap_or(0);
+                module.member_decorations.insert((operand(0)?, operand(1)?, operand(2)?), value);
+            }
+            _ => {}
+        }
+
+        offset += count;
+    }
+
+    Ok(module)
+}
+
+impl Module {
+    fn get_type(&self, id: u32) -> Result<&Type> {
+        self.types.get(&id).ok_or_else(|| anyhow!("Unknown type %{}.", id))
+    }
+
+    fn get_constant(&self, id: u32) -> Result<u32> {
+        self.constants.get(&id).copied().ok_or_else(|| anyhow!("Unknown constant %{}.", id))
+    }
+
+    /// Returns the type `id` nested `depth` types deep, failing if that is
+    /// too deep.
+    fn get_nested_type(&self, id: u32, depth: u32) -> Result<&Type> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err(anyhow!("Type %{} is nested too deeply, or contains itself.", id));
+        }
+
+        self.get_type(id)
+    }
+
+    fn get_descriptor_type(
+        &self,
+        id: u32,
+        storage_class: u32,
+        depth: u32,
+    ) -> Result<(vk::DescriptorType, u32)> {
+        let descriptor_type = match (self.get_nested_type(id, depth)?, storage_class) {
+            (Type::Array { element, length }, _) => {
+                let (descriptor_type, count) = self.get_descriptor_type(*element, storage_class, depth + 1)?;
+                return Ok((descriptor_type, multiply(count, self.get_constant(*length)?)?));
+            }
+            (Type::RuntimeArray { .. }, _) => {
+                return Err(anyhow!("Runtime-sized descriptor arrays are not supported."));
+            }
+            (Type::SampledImage, _) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (Type::Sampler, _) => vk::DescriptorType::SAMPLER,
+            (Type::Image { dim: DIM_SUBPASS_DATA, .. }, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+            (Type::Image { dim: DIM_BUFFER, sampled: 2 }, _) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+            (Type::Image { dim: DIM_BUFFER, .. }, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+            (Type::Image { sampled: 2, .. }, _) => vk::DescriptorType::STORAGE_IMAGE,
+            (Type::Image { .. }, _) => vk::DescriptorType::SAMPLED_IMAGE,
+            (Type::Struct { .. }, STORAGE_CLASS_STORAGE_BUFFER) => vk::DescriptorType::STORAGE_BUFFER,
+            (Type::Struct { .. }, _) if self.decorations.contains_key(&(id, DECORATION_BUFFER_BLOCK)) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            (Type::Struct { .. }, _) if self.decorations.contains_key(&(id, DECORATION_BLOCK)) => {
+                vk::DescriptorType::UNIFORM_BUFFER
+            }
+            (ty, _) => return Err(anyhow!("Type {:?} cannot be bound through a descriptor.", ty)),
+        };
+
+        Ok((descriptor_type, 1))
+    }
+
+    /// Returns the size in bytes of the type `id` in a block. Matrices need
+    /// the `matrix_stride` of the member they are declared as.
+    fn get_size(&self, id: u32, matrix_stride: Option<u32>, depth: u32) -> Result<u32> {
+        Ok(match self.get_nested_type(id, depth)? {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector { component, count } => multiply(self.get_size(*component, None, depth + 1)?, *count)?,
+            Type::Matrix { column, count } => match matrix_stride {
+                Some(stride) => multiply(stride, *count)?,
+                None => multiply(self.get_size(*column, None, depth + 1)?, *count)?,
+            },
+            Type::Array { element, length } => {
+                let stride = match self.decorations.get(&(id, DECORATION_ARRAY_STRIDE)) {
+                    Some(stride) => *stride,
+                    None => self.get_size(*element, None, depth + 1)?,
+                };
+                multiply(stride, self.get_constant(*length)?)?
+            }
+            Type::Struct { members } => {
+                let mut size = 0;
+                for (i, member) in members.iter().enumerate() {
+                    let i = i as u32;
+                    let offset = self.member_decorations.get(&(id, i, DECORATION_OFFSET)).copied().unwrap_or(0);
+                    let stride = self.member_decorations.get(&(id, i, DECORATION_MATRIX_STRIDE)).copied();
+                    let end = offset
+                        .checked_add(self.get_size(*member, stride, depth + 1)?)
+                        .ok_or_else(|| anyhow!("The size of type %{} overflows.", id))?;
+                    size = size.max(end);
+                }
+                size
+            }
+            ty => return Err(anyhow!("Type {:?} has no size in a block.", ty)),
+        })
+    }
+
+    /// Returns the format of a stage input or output of the type `id`.
+    fn get_format(&self, id: u32) -> Result<vk::Format> {
+        let (component, count) = match self.get_type(id)? {
+            Type::Vector { component, count } => (*component, *count),
+            Type::Int { .. } | Type::Float { .. } => (id, 1),
+            ty => return Err(anyhow!("Stage inputs and outputs of type {:?} are not supported.", ty)),
+        };
+
+        use vk::Format as F;
+        let formats = match self.get_type(component)? {
+            Type::Float { width: 32 } => [F::R32_SFLOAT, F::R32G32_SFLOAT, F::R32G32B32_SFLOAT, F::R32G32B32A32_SFLOAT],
+            Type::Int { width: 32, signed: true } => [F::R32_SINT, F::R32G32_SINT, F::R32G32B32_SINT, F::R32G32B32A32_SINT],
+            Type::Int { width: 32, signed: false } => [F::R32_UINT, F::R32G32_UINT, F::R32G32B32_UINT, F::R32G32B32A32_UINT],
+            ty => return Err(anyhow!("Stage inputs and outputs of type {:?} are not supported.", ty)),
+        };
+
+        (count as usize)
+            .checked_sub(1)
+            .and_then(|i| formats.get(i))
+            .copied()
+            .ok_or_else(|| anyhow!("Invalid vector size {}.", count))
+    }
+}
+
+/// The size in bytes of a stage input in one of the formats `get_format`
+/// reflects them as.
+fn get_format_size(format: vk::Format) -> Result<u32> {
+    use vk::Format as F;
+    match format {
+        F::R32_SFLOAT | F::R32_SINT | F::R32_UINT => Ok(4),
+        F::R32G32_SFLOAT | F::R32G32_SINT | F::R32G32_UINT => Ok(8),
+        F::R32G32B32_SFLOAT | F::R32G32B32_SINT | F::R32G32B32_UINT => Ok(12),
+        F::R32G32B32A32_SFLOAT | F::R32G32B32A32_SINT | F::R32G32B32A32_UINT => Ok(16),
+        _ => Err(anyhow!("The size of {:?} is unknown.", format)),
+    }
+}
+
+/// Multiplies sizes or counts, failing instead of overflowing.
+fn multiply(a: u32, b: u32) -> Result<u32> {
+    a.checked_mul(b).ok_or_else(|| anyhow!("{} x {} overflows.", a, b))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::shaders::{load_shader, FRAGMENT_SHADER, VERTEX_SHADER};
+    use crate::vertex::Vertex;
+
+    const OP_TYPE_FLOAT_32: [u32; 3] = [(3 << 16) | OP_TYPE_FLOAT, 1, 32];
+    const OP_ENTRY_POINT_VERTEX: [u32; 4] = [(4 << 16) | OP_ENTRY_POINT, 0, 100, 0];
+
+    /// Assembles a module from `instructions` behind a SPIR-V header.
+    fn module(instructions: &[&[u32]]) -> Vec<u8> {
+        [MAGIC, 0x0001_0000, 0, 200, 0]
+            .iter()
+            .chain(instructions.iter().flat_map(|i| i.iter()))
+            .flat_map(|w| w.to_le_bytes())
+            .collect()
+    }
+
+    fn built_in_layout() -> ShaderLayout {
+        let vert = load_shader(None, VERTEX_SHADER).unwrap();
+        let frag = load_shader(None, FRAGMENT_SHADER).unwrap();
+        ShaderLayout::reflect(&[&vert, &frag]).unwrap()
+    }
+
+    #[test]
+    fn test_reflect_built_in_shaders() {
+        let layout = built_in_layout();
+
+        let bindings = layout.bindings
+            .iter()
+            .map(|b| (b.set, b.binding, b.descriptor_type, b.count, b.stages))
+            .collect::<Vec<_>>();
+        assert_eq!(bindings, [
+            (0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1, vk::ShaderStageFlags::VERTEX),
+            (1, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1, vk::ShaderStageFlags::FRAGMENT),
+        ]);
+
+        assert_eq!(layout.vertex_inputs, [
+            (0, vk::Format::R32G32B32_SFLOAT),
+            (1, vk::Format::R32G32B32_SFLOAT),
+            (2, vk::Format::R32G32B32_SFLOAT),
+            (3, vk::Format::R32G32_SFLOAT),
+        ]);
+
+        let push_constants = layout.push_constants.unwrap();
+        assert_eq!(push_constants.stage_flags, vk::ShaderStageFlags::VERTEX);
+        assert_eq!(push_constants.offset, 0);
+        assert_eq!(push_constants.size, 68);
+    }
+
+    #[test]
+    fn test_get_vertex_input() {
+        let (binding, attributes) = built_in_layout().get_vertex_input(1).unwrap();
+        assert_eq!(binding.binding, 1);
+        assert_eq!(binding.stride, 44);
+        assert_eq!(binding.input_rate, vk::VertexInputRate::VERTEX);
+
+        let attributes = attributes
+            .iter()
+            .map(|a| (a.binding, a.location, a.format, a.offset))
+            .collect::<Vec<_>>();
+        assert_eq!(attributes, [
+            (1, 0, vk::Format::R32G32B32_SFLOAT, 0),
+            (1, 1, vk::Format::R32G32B32_SFLOAT, 12),
+            (1, 2, vk::Format::R32G32B32_SFLOAT, 24),
+            (1, 3, vk::Format::R32G32_SFLOAT, 36),
+        ]);
+    }
+
+    #[test]
+    fn test_built_in_shaders_match_vertex() {
+        assert!(Vertex::check_shader_layout(&built_in_layout()).is_ok());
+
+        let mut changed = built_in_layout();
+        changed.vertex_inputs[3].1 = vk::Format::R32G32B32_SFLOAT;
+        assert!(Vertex::check_shader_layout(&changed).is_err());
+    }
+
+    #[test]
+    fn test_check_unchanged() {
+        let layout = built_in_layout();
+        assert!(layout.check_unchanged(&layout).is_ok());
+
+        let mut changed = layout.clone();
+        changed.vertex_inputs.pop();
+        assert!(changed.check_unchanged(&layout).is_err());
+
+        let mut changed = layout.clone();
+        changed.push_constants.as_mut().unwrap().size += 4;
+        assert!(changed.check_unchanged(&layout).is_err());
+
+        let mut changed = layout.clone();
+        changed.bindings[1].descriptor_type = vk::DescriptorType::SAMPLED_IMAGE;
+        assert!(changed.check_unchanged(&layout).is_err());
+    }
+
+    #[test]
+    fn test_reflect_rejects_truncated_input() {
+        let vert = load_shader(None, VERTEX_SHADER).unwrap();
+
+        assert!(reflect(&[]).is_err());
+        assert!(reflect(&vert[..3]).is_err());
+        assert!(reflect(&vert[..16]).is_err());
+        assert!(reflect(&vert[..vert.len() - 1]).is_err());
+
+        // The first instruction after the header, cut after its first word.
+        assert!(reflect(&vert[..24]).is_err());
+
+        // No prefix may panic, whether or not it still parses.
+        for len in (0..=vert.len()).step_by(4) {
+            let _ = reflect(&vert[..len]);
+        }
+    }
+
+    #[test]
+    fn test_reflect_rejects_garbage() {
+        assert!(reflect(&[0xAB; 64]).is_err());
+        assert!(reflect(&module(&[&[0xFFFF_FFFF; 8]])).is_err());
+        assert!(reflect(&module(&[&[0; 8]])).is_err());
+
+        // No garbage may panic, whether or not it still parses.
+        let mut state = 0x1234_5678u32;
+        for _ in 0..1000 {
+            let words = (0..16)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state
+                })
+                .collect::<Vec<_>>();
+            let _ = reflect(&module(&[&OP_ENTRY_POINT_VERTEX, &words]));
+        }
+    }
+
+    #[test]
+    fn test_reflect_rejects_empty_struct() {
+        let bytecode = module(&[&OP_ENTRY_POINT_VERTEX, &[(1 << 16) | OP_TYPE_STRUCT]]);
+        assert!(reflect(&bytecode).is_err());
+    }
+
+    #[test]
+    fn test_reflect_rejects_cyclic_type() {
+        let bytecode = module(&[
+            &OP_ENTRY_POINT_VERTEX,
+            // %2 = struct { %2 }, pushed as push constants.
+            &[(3 << 16) | OP_TYPE_STRUCT, 2, 2],
+            &[(4 << 16) | OP_TYPE_POINTER, 3, STORAGE_CLASS_PUSH_CONSTANT, 2],
+            &[(4 << 16) | OP_VARIABLE, 3, 4, STORAGE_CLASS_PUSH_CONSTANT],
+        ]);
+        assert!(reflect(&bytecode).is_err());
+    }
+
+    #[test]
+    fn test_reflect_rejects_empty_vector_input() {
+        let bytecode = module(&[
+            &OP_ENTRY_POINT_VERTEX,
+            &OP_TYPE_FLOAT_32,
+            &[(4 << 16) | OP_TYPE_VECTOR, 2, 1, 0],
+            &[(4 << 16) | OP_TYPE_POINTER, 3, STORAGE_CLASS_INPUT, 2],
+            &[(4 << 16) | OP_VARIABLE, 3, 4, STORAGE_CLASS_INPUT],
+            &[(4 << 16) | OP_DECORATE, 4, DECORATION_LOCATION, 0],
+        ]);
+        assert!(reflect(&bytecode).is_err());
+    }
+
+    #[test]
+    fn test_reflect_rejects_overflowing_size() {
+        let bytecode = module(&[
+            &OP_ENTRY_POINT_VERTEX,
+            &OP_TYPE_FLOAT_32,
+            &[(4 << 16) | OP_CONSTANT, 1, 5, u32::MAX],
+            &[(4 << 16) | OP_TYPE_ARRAY, 2, 1, 5],
+            &[(4 << 16) | OP_TYPE_POINTER, 3, STORAGE_CLASS_PUSH_CONSTANT, 2],
+            &[(4 << 16) | OP_VARIABLE, 3, 4, STORAGE_CLASS_PUSH_CONSTANT],
+        ]);
+        assert!(reflect(&bytecode).is_err());
+    }
+
+    #[test]
+    fn test_reflect_rejects_overflowing_descriptor_count() {
+        let bytecode = module(&[
+            &OP_ENTRY_POINT_VERTEX,
+            &[(2 << 16) | OP_TYPE_SAMPLER, 1],
+            &[(4 << 16) | OP_CONSTANT, 6, 5, 0x1_0000],
+            &[(4 << 16) | OP_TYPE_ARRAY, 2, 1, 5],
+            &[(4 << 16) | OP_TYPE_ARRAY, 7, 2, 5],
+            &[(4 << 16) | OP_TYPE_POINTER, 3, STORAGE_CLASS_UNIFORM_CONSTANT, 7],
+            &[(4 << 16) | OP_VARIABLE, 3, 4, STORAGE_CLASS_UNIFORM_CONSTANT],
+            &[(4 << 16) | OP_DECORATE, 4, DECORATION_DESCRIPTOR_SET, 0],
+            &[(4 << 16) | OP_DECORATE, 4, DECORATION_BINDING, 0],
+        ]);
+        assert!(reflect(&bytecode).is_err());
+    }
+}
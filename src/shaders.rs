@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+
+use crate::reflect::ShaderLayout;
+
+/// The file name of the compiled vertex shader.
+pub const VERTEX_SHADER: &str = "shader.vert.spv";
+/// The file name of the compiled fragment shader.
+pub const FRAGMENT_SHADER: &str = "shader.frag.spv";
+
+/// Loads the SPIR-V of the shader `name` from `dir`, or the copy compiled
+/// into the binary by our build script if there is no shader directory.
+pub fn load_shader(dir: Option<&Path>, name: &str) -> Result<Vec<u8>> {
+    let Some(dir) = dir else {
+        return get_embedded_shader(name).map(|b| b.to_vec());
+    };
+
+    let path = dir.join(name);
+    fs::read(&path).map_err(|e| anyhow!(
+        "Failed to read shader `{}`: {}. The shader directory has to hold SPIR-V \
+        compiled from `shaders/`, e.g. with `glslangValidator -V`.",
+        path.display(),
+        e,
+    ))
+}
+
+/// Reflects the interface of the shaders our pipelines are built from.
+pub fn reflect_shaders(dir: Option<&Path>) -> Result<ShaderLayout> {
+    let vert = load_shader(dir, VERTEX_SHADER)?;
+    let frag = load_shader(dir, FRAGMENT_SHADER)?;
+    ShaderLayout::reflect(&[&vert, &frag])
+}
+
+fn get_embedded_shader(name: &str) -> Result<&'static [u8]> {
+    match name {
+        VERTEX_SHADER => Ok(include_bytes!(concat!(env!("OUT_DIR"), "/shader.vert.spv"))),
+        FRAGMENT_SHADER => Ok(include_bytes!(concat!(env!("OUT_DIR"), "/shader.frag.spv"))),
+        _ => Err(anyhow!("There is no built-in shader `{}`.", name)),
+    }
+}
+
+/// Watches the modification times of shaders in a directory.
+#[derive(Clone, Debug)]
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    /// The last seen modification time of each shader, if it exists.
+    modified: HashMap<String, Option<SystemTime>>,
+}
+
+impl ShaderWatcher {
+    /// Starts watching the shaders `names` in `dir`.
+    pub fn new(dir: &Path, names: &[&str]) -> Self {
+        let modified = names
+            .iter()
+            .map(|n| (n.to_string(), get_modified(&dir.join(n))))
+            .collect();
+
+        Self { dir: dir.to_path_buf(), modified }
+    }
+
+    /// Returns the names of the shaders that changed since the last poll.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut changed = vec![];
+        for (name, modified) in &mut self.modified {
+            let current = get_modified(&self.dir.join(name));
+            if current != *modified {
+                *modified = current;
+                changed.push(name.clone());
+            }
+        }
+
+        changed.sort();
+        changed
+    }
+}
+
+fn get_modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -1,13 +1,15 @@
-use vulkanalia::{vk::{self, DeviceV1_0, Handle, HasBuilder, KhrSwapchainExtension}, Device, Instance};
+use std::sync::Arc;
+
+use vulkanalia::{vk::{self, DeviceV1_0, HasBuilder, KhrSwapchainExtension}, Instance};
 use winit::window::Window;
 
-use crate::{appdata::AppData, queue_family_indices::QueueFamilyIndices, swapchain_support::{get_swapchain_extent, get_swapchain_present_mode, get_swapchain_surface_format, SwapchainSupport}};
+use crate::{appdata::AppData, owned::{Owned, OwnedDevice}, queue_family_indices::QueueFamilyIndices, swapchain_support::{get_swapchain_extent, get_swapchain_present_mode, get_swapchain_surface_format, SwapchainSupport}};
 use anyhow::Result;
 
 pub unsafe fn create_swapchain(
     window: &Window,
     instance: &Instance,
-    device: &Device,
+    device: &Arc<OwnedDevice>,
     data: &mut AppData,
 ) -> Result<()> {
     let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
@@ -47,10 +49,10 @@ pub unsafe fn create_swapchain(
         .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
         .present_mode(present_mode)
         .clipped(true)
-        .old_swapchain(vk::SwapchainKHR::null());
+        .old_swapchain(*data.swapchain);
 
-        data.swapchain = device.create_swapchain_khr(&info, None)?;
-        data.swapchain_images = device.get_swapchain_images_khr(data.swapchain)?;
+        data.swapchain = Owned::new(device, device.create_swapchain_khr(&info, None)?);
+        data.swapchain_images = device.get_swapchain_images_khr(*data.swapchain)?;
         data.swapchain_format = surface_format.format;
         data.swapchain_extent = extent;
         
@@ -58,37 +60,36 @@ pub unsafe fn create_swapchain(
 }
 
 pub unsafe fn create_swapchain_image_views(
-    device: &Device,
+    device: &Arc<OwnedDevice>,
     data: &mut AppData,
 ) -> Result<()> {
-    data.swapchain_image_views = data
-        .swapchain_images
-        .iter()
-        .map(|i| {
-            let components = vk::ComponentMapping::builder()
-                .r(vk::ComponentSwizzle::IDENTITY)
-                .g(vk::ComponentSwizzle::IDENTITY)
-                .b(vk::ComponentSwizzle::IDENTITY)
-                .a(vk::ComponentSwizzle::IDENTITY);
+    // Pushed as they are created, so a failure destroys the earlier ones.
+    let mut image_views = Owned::new(device, vec![]);
+    for i in &data.swapchain_images {
+        let components = vk::ComponentMapping::builder()
+            .r(vk::ComponentSwizzle::IDENTITY)
+            .g(vk::ComponentSwizzle::IDENTITY)
+            .b(vk::ComponentSwizzle::IDENTITY)
+            .a(vk::ComponentSwizzle::IDENTITY);
+
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
 
-            let subresource_range = vk::ImageSubresourceRange::builder()
-                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                .base_mip_level(0)
-                .level_count(1)
-                .base_array_layer(0)
-                .layer_count(1);
-            
-            let info = vk::ImageViewCreateInfo::builder()
-                .image(*i)
-                .view_type(vk::ImageViewType::_2D)
-                .format(data.swapchain_format)
-                .components(components)
-                .subresource_range(subresource_range);
+        let info = vk::ImageViewCreateInfo::builder()
+            .image(*i)
+            .view_type(vk::ImageViewType::_2D)
+            .format(data.swapchain_format)
+            .components(components)
+            .subresource_range(subresource_range);
+
+        image_views.push(device.create_image_view(&info, None)?);
+    }
 
-            device.create_image_view(&info, None)
-            
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+    data.swapchain_image_views = image_views;
 
     Ok(())
 }
@@ -0,0 +1,488 @@
+use std::fs::File;
+use std::path::Path;
+use std::ptr::copy_nonoverlapping as memcpy;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+use crate::buffers::create_buffer;
+use crate::commands::{begin_single_time_commands, end_single_time_commands};
+use crate::images::{copy_buffer_to_image, create_image, create_image_view, transition_image_layout};
+use crate::owned::{Destroy, Owned, OwnedDevice};
+
+/// The format textures are uploaded in. PNG color data is sRGB encoded.
+pub const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Decoded RGBA8 pixels, tightly packed row by row.
+#[derive(Clone, Debug)]
+pub struct Pixels {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// A sampled image together with the descriptor set exposing it to shaders.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Texture {
+    pub image: vk::Image,
+    pub image_memory: vk::DeviceMemory,
+    pub image_view: vk::ImageView,
+    pub sampler: vk::Sampler,
+    pub mip_levels: u32,
+    pub descriptor_set: vk::DescriptorSet,
+}
+
+impl Texture {
+    /// Uploads `pixels` into a new device-local image with a full mip chain
+    /// and creates a view and sampler for it. The descriptor set is
+    /// allocated separately.
+    pub unsafe fn create(
+        instance: &Instance,
+        device: &Arc<OwnedDevice>,
+        data: &AppData,
+        pixels: &Pixels,
+    ) -> Result<Self> {
+        let mip_levels = get_mip_levels(pixels.width, pixels.height);
+
+        // Blitting with linear filtering is the fast path. Without it, the
+        // smaller levels are computed here and uploaded with the first one.
+        let blit = supports_linear_blit(instance, data, TEXTURE_FORMAT);
+        let smaller_levels = if blit { vec![] } else { get_smaller_mip_levels(pixels, mip_levels) };
+        let levels = std::iter::once(pixels).chain(&smaller_levels).collect::<Vec<_>>();
+
+        let size = levels.iter().map(|l| l.rgba.len()).sum::<usize>() as vk::DeviceSize;
+
+        // Everything is owned until the texture is complete, so a failed step
+        // destroys what was created before it.
+        let (staging_buffer, staging_buffer_memory) = create_buffer(
+            instance,
+            device,
+            data,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?;
+
+        let memory = device.map_memory(*staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())?;
+        let mut offset = 0;
+        for level in &levels {
+            memcpy(level.rgba.as_ptr(), memory.cast::<u8>().add(offset), level.rgba.len());
+            offset += level.rgba.len();
+        }
+        device.unmap_memory(*staging_buffer_memory);
+
+        let (image, image_memory) = create_image(
+            instance,
+            device,
+            data,
+            pixels.width,
+            pixels.height,
+            mip_levels,
+            vk::SampleCountFlags::_1,
+            TEXTURE_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        transition_image_layout(
+            device,
+            data,
+            *image,
+            mip_levels,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        )?;
+
+        let mut offset = 0;
+        for (mip_level, level) in levels.iter().enumerate() {
+            copy_buffer_to_image(
+                device,
+                data,
+                *staging_buffer,
+                offset,
+                *image,
+                mip_level as u32,
+                level.width,
+                level.height,
+            )?;
+            offset += level.rgba.len() as vk::DeviceSize;
+        }
+
+        if blit {
+            generate_mipmaps(device, data, *image, pixels.width, pixels.height, mip_levels)?;
+        } else {
+            transition_image_layout(
+                device,
+                data,
+                *image,
+                mip_levels,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )?;
+        }
+
+        // The staging buffer is only needed for the upload.
+        drop(staging_buffer);
+        drop(staging_buffer_memory);
+
+        let image_view = create_image_view(device, *image, TEXTURE_FORMAT, vk::ImageAspectFlags::COLOR, mip_levels)?;
+        let image_view = Owned::new(device, image_view);
+        let sampler = Owned::new(device, create_sampler(instance, device, data, mip_levels)?);
+
+        Ok(Self {
+            image: image.into_inner(),
+            image_memory: image_memory.into_inner(),
+            image_view: image_view.into_inner(),
+            sampler: sampler.into_inner(),
+            mip_levels,
+            descriptor_set: vk::DescriptorSet::default(),
+        })
+    }
+}
+
+impl Destroy for Texture {
+    unsafe fn destroy(&self, device: &Device) {
+        device.destroy_sampler(self.sampler, None);
+        device.destroy_image_view(self.image_view, None);
+        device.destroy_image(self.image, None);
+        device.free_memory(self.image_memory, None);
+    }
+}
+
+/// The number of levels in a full mip chain for an image of the given size.
+pub fn get_mip_levels(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+unsafe fn supports_linear_blit(instance: &Instance, data: &AppData, format: vk::Format) -> bool {
+    instance
+        .get_physical_device_format_properties(data.physical_device, format)
+        .optimal_tiling_features
+        .contains(
+            vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR
+                | vk::FormatFeatureFlags::BLIT_SRC
+                | vk::FormatFeatureFlags::BLIT_DST,
+        )
+}
+
+/// Fills mip levels 1 and up by repeatedly blitting each level into the
+/// next, leaving every level in `SHADER_READ_ONLY_OPTIMAL`.
+///
+/// Every level must be in `TRANSFER_DST_OPTIMAL` with level 0 filled in.
+unsafe fn generate_mipmaps(
+    device: &Device,
+    data: &AppData,
+    image: vk::Image,
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+) -> Result<()> {
+    let command_buffer = begin_single_time_commands(device, data)?;
+
+    let subresource = vk::ImageSubresourceRange::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_array_layer(0)
+        .layer_count(1)
+        .level_count(1);
+
+    let mut barrier = vk::ImageMemoryBarrier::builder()
+        .image(image)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .subresource_range(subresource);
+
+    let mut mip_width = width as i32;
+    let mut mip_height = height as i32;
+
+    for i in 1..mip_levels {
+        barrier.subresource_range.base_mip_level = i - 1;
+        barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+        barrier.new_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+        barrier.dst_access_mask = vk::AccessFlags::TRANSFER_READ;
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[] as &[vk::MemoryBarrier],
+            &[] as &[vk::BufferMemoryBarrier],
+            &[barrier],
+        );
+
+        let src_subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(i - 1)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let dst_subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(i)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let next_width = (mip_width / 2).max(1);
+        let next_height = (mip_height / 2).max(1);
+
+        let blit = vk::ImageBlit::builder()
+            .src_offsets([
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D { x: mip_width, y: mip_height, z: 1 },
+            ])
+            .src_subresource(src_subresource)
+            .dst_offsets([
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D { x: next_width, y: next_height, z: 1 },
+            ])
+            .dst_subresource(dst_subresource);
+
+        device.cmd_blit_image(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[blit],
+            vk::Filter::LINEAR,
+        );
+
+        barrier.old_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        barrier.src_access_mask = vk::AccessFlags::TRANSFER_READ;
+        barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[] as &[vk::MemoryBarrier],
+            &[] as &[vk::BufferMemoryBarrier],
+            &[barrier],
+        );
+
+        mip_width = next_width;
+        mip_height = next_height;
+    }
+
+    // The last level is only ever blitted into, never from.
+    barrier.subresource_range.base_mip_level = mip_levels - 1;
+    barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+    barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+    barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+    barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::DependencyFlags::empty(),
+        &[] as &[vk::MemoryBarrier],
+        &[] as &[vk::BufferMemoryBarrier],
+        &[barrier],
+    );
+
+    end_single_time_commands(device, data, command_buffer)?;
+
+    Ok(())
+}
+
+/// Computes mip levels 1 and up on the CPU by averaging 2x2 blocks of the
+/// previous level, for formats the device cannot blit with filtering.
+fn get_smaller_mip_levels(pixels: &Pixels, mip_levels: u32) -> Vec<Pixels> {
+    let mut levels: Vec<Pixels> = Vec::with_capacity(mip_levels as usize - 1);
+    for _ in 1..mip_levels {
+        let previous = levels.last().unwrap_or(pixels);
+        levels.push(downsample(previous));
+    }
+    levels
+}
+
+/// Halves both dimensions of sRGB encoded `pixels`, averaging in linear space.
+fn downsample(pixels: &Pixels) -> Pixels {
+    let width = (pixels.width / 2).max(1);
+    let height = (pixels.height / 2).max(1);
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+
+    let texel = |x: u32, y: u32, c: usize| {
+        let x = x.min(pixels.width - 1);
+        let y = y.min(pixels.height - 1);
+        pixels.rgba[((y * pixels.width + x) * 4) as usize + c]
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            for c in 0..4 {
+                let samples = [
+                    texel(2 * x, 2 * y, c),
+                    texel(2 * x + 1, 2 * y, c),
+                    texel(2 * x, 2 * y + 1, c),
+                    texel(2 * x + 1, 2 * y + 1, c),
+                ];
+
+                let value = if c == 3 {
+                    samples.iter().map(|s| *s as f32 / 255.0).sum::<f32>() / 4.0
+                } else {
+                    let linear = samples.iter().map(|s| srgb_to_linear(*s as f32 / 255.0)).sum::<f32>() / 4.0;
+                    linear_to_srgb(linear)
+                };
+
+                rgba.push((value * 255.0).round() as u8);
+            }
+        }
+    }
+
+    Pixels { width, height, rgba }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+unsafe fn create_sampler(
+    instance: &Instance,
+    device: &Device,
+    data: &AppData,
+    mip_levels: u32,
+) -> Result<vk::Sampler> {
+    let anisotropy = data.enabled_features.sampler_anisotropy == vk::TRUE;
+    let max_anisotropy = instance
+        .get_physical_device_properties(data.physical_device)
+        .limits
+        .max_sampler_anisotropy
+        .min(16.0);
+
+    let info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .address_mode_u(vk::SamplerAddressMode::REPEAT)
+        .address_mode_v(vk::SamplerAddressMode::REPEAT)
+        .address_mode_w(vk::SamplerAddressMode::REPEAT)
+        .anisotropy_enable(anisotropy)
+        .max_anisotropy(if anisotropy { max_anisotropy } else { 1.0 })
+        .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+        .unnormalized_coordinates(false)
+        .compare_enable(false)
+        .compare_op(vk::CompareOp::ALWAYS)
+        .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+        .mip_lod_bias(0.0)
+        .min_lod(0.0)
+        .max_lod(mip_levels as f32);
+
+    Ok(device.create_sampler(&info, None)?)
+}
+
+/// Decodes a PNG file into RGBA8 pixels, expanding palette, grayscale and
+/// 16-bit images.
+pub fn load_png(path: &Path) -> Result<Pixels> {
+    let file = File::open(path)
+        .map_err(|e| anyhow!("Failed to open texture `{}`: {}.", path.display(), e))?;
+
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let error = |e: png::DecodingError| anyhow!("Failed to decode texture `{}`: {}.", path.display(), e);
+    let mut reader = decoder.read_info().map_err(error)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(error)?;
+    buffer.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer
+            .iter()
+            .flat_map(|p| [*p, *p, *p, 255])
+            .collect(),
+        png::ColorType::Indexed => {
+            return Err(anyhow!("Texture `{}` has an unexpanded palette.", path.display()));
+        }
+    };
+
+    Ok(Pixels { width: info.width, height: info.height, rgba })
+}
+
+/// Creates the layout of set 1 from the shaders, which must sample the
+/// mesh's texture through binding 0.
+pub unsafe fn create_texture_set_layout(device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
+    data.shader_layout.check_set(1, &[(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)])?;
+
+    let bindings = &data.shader_layout.get_set_layout_bindings(1);
+    let info = vk::DescriptorSetLayoutCreateInfo::builder()
+        .bindings(bindings);
+
+    data.texture_set_layout = Owned::new(device, device.create_descriptor_set_layout(&info, None)?);
+
+    Ok(())
+}
+
+/// Allocates and writes one descriptor set per texture in `data.textures`.
+pub unsafe fn create_texture_descriptor_sets(device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
+    let count = data.textures.len() as u32;
+
+    let sampler_size = vk::DescriptorPoolSize::builder()
+        .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(count);
+
+    let pool_sizes = &[sampler_size];
+    let info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(pool_sizes)
+        .max_sets(count);
+
+    data.texture_descriptor_pool = Owned::new(device, device.create_descriptor_pool(&info, None)?);
+
+    let layouts = vec![*data.texture_set_layout; data.textures.len()];
+    let info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(*data.texture_descriptor_pool)
+        .set_layouts(&layouts);
+
+    let descriptor_sets = device.allocate_descriptor_sets(&info)?;
+
+    for (texture, descriptor_set) in data.textures.iter_mut().zip(descriptor_sets) {
+        texture.descriptor_set = descriptor_set;
+
+        let info = vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(texture.image_view)
+            .sampler(texture.sampler);
+
+        let image_info = &[info];
+        let sampler_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(image_info);
+
+        device.update_descriptor_sets(&[sampler_write], &[] as &[vk::CopyDescriptorSet]);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::mem::size_of;
+use std::sync::Arc;
+use std::ptr::copy_nonoverlapping as memcpy;
+
+use anyhow::Result;
+use cgmath::{point3, vec3, Deg, Matrix4};
+use vulkanalia::vk::{self, DeviceV1_0, HasBuilder};
+use vulkanalia::{Device, Instance};
+
+use crate::appdata::AppData;
+use crate::buffers::create_buffer;
+use crate::owned::{Owned, OwnedDevice};
+
+pub type Mat4 = Matrix4<f32>;
+
+/// The transforms shared by every draw, bound at set 0, binding 0.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct UniformBufferObject {
+    pub model: Mat4,
+    pub view: Mat4,
+    pub proj: Mat4,
+}
+
+impl UniformBufferObject {
+    /// Builds the transforms for `time` seconds into the app, rendering to
+    /// a target of `extent`.
+    pub fn at_time(time: f32, extent: vk::Extent2D) -> Self {
+        let model = Mat4::from_axis_angle(vec3(0.0, 0.0, 1.0), Deg(90.0) * time);
+
+        let view = Mat4::look_at_rh(
+            point3(2.0, 2.0, 2.0),
+            point3(0.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+        );
+
+        // cgmath follows OpenGL, whose clip space has Y pointing up and depth
+        // in [-1, 1]. Vulkan's has Y pointing down and depth in [0, 1].
+        #[rustfmt::skip]
+        let correction = Mat4::new(
+            1.0,  0.0,       0.0, 0.0,
+            0.0, -1.0,       0.0, 0.0,
+            0.0,  0.0, 1.0 / 2.0, 0.0,
+            0.0,  0.0, 1.0 / 2.0, 1.0,
+        );
+
+        let aspect = extent.width as f32 / extent.height as f32;
+        let proj = correction * cgmath::perspective(Deg(45.0), aspect, 0.1, 10.0);
+
+        Self { model, view, proj }
+    }
+}
+
+/// Creates the layout of set 0 from the shaders, which must read the
+/// `UniformBufferObject` through binding 0.
+pub unsafe fn create_descriptor_set_layout(device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
+    data.shader_layout.check_set(0, &[(0, vk::DescriptorType::UNIFORM_BUFFER)])?;
+
+    let bindings = &data.shader_layout.get_set_layout_bindings(0);
+    let info = vk::DescriptorSetLayoutCreateInfo::builder()
+        .bindings(bindings);
+
+    data.descriptor_set_layout = Owned::new(device, device.create_descriptor_set_layout(&info, None)?);
+
+    Ok(())
+}
+
+/// Creates one uniform buffer per swapchain image, so a buffer is never
+/// written while a frame that reads it is still in flight.
+pub unsafe fn create_uniform_buffers(
+    instance: &Instance,
+    device: &Arc<OwnedDevice>,
+    data: &mut AppData,
+) -> Result<()> {
+    data.uniform_buffers = Owned::new(device, vec![]);
+    data.uniform_buffers_memory = Owned::new(device, vec![]);
+
+    for _ in 0..data.swapchain_images.len() {
+        let (uniform_buffer, uniform_buffer_memory) = create_buffer(
+            instance,
+            device,
+            data,
+            size_of::<UniformBufferObject>() as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?;
+
+        data.uniform_buffers.push(uniform_buffer.into_inner());
+        data.uniform_buffers_memory.push(uniform_buffer_memory.into_inner());
+    }
+
+    Ok(())
+}
+
+pub unsafe fn create_descriptor_pool(device: &Arc<OwnedDevice>, data: &mut AppData) -> Result<()> {
+    let ubo_size = vk::DescriptorPoolSize::builder()
+        .type_(vk::DescriptorType::UNIFORM_BUFFER)
+        .descriptor_count(data.swapchain_images.len() as u32);
+
+    let pool_sizes = &[ubo_size];
+    let info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(pool_sizes)
+        .max_sets(data.swapchain_images.len() as u32);
+
+    data.descriptor_pool = Owned::new(device, device.create_descriptor_pool(&info, None)?);
+
+    Ok(())
+}
+
+pub unsafe fn create_descriptor_sets(device: &Device, data: &mut AppData) -> Result<()> {
+    let layouts = vec![*data.descriptor_set_layout; data.swapchain_images.len()];
+    let info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(*data.descriptor_pool)
+        .set_layouts(&layouts);
+
+    data.descriptor_sets = device.allocate_descriptor_sets(&info)?;
+
+    for (descriptor_set, uniform_buffer) in data.descriptor_sets.iter().zip(data.uniform_buffers.iter()) {
+        let info = vk::DescriptorBufferInfo::builder()
+            .buffer(*uniform_buffer)
+            .offset(0)
+            .range(size_of::<UniformBufferObject>() as vk::DeviceSize);
+
+        let buffer_info = &[info];
+        let ubo_write = vk::WriteDescriptorSet::builder()
+            .dst_set(*descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(buffer_info);
+
+        device.update_descriptor_sets(&[ubo_write], &[] as &[vk::CopyDescriptorSet]);
+    }
+
+    Ok(())
+}
+
+/// Writes the transforms for `time` into the uniform buffer of `image_index`.
+pub unsafe fn update_uniform_buffer(
+    device: &Device,
+    data: &AppData,
+    image_index: usize,
+    time: f32,
+) -> Result<()> {
+    let ubo = UniformBufferObject::at_time(time, data.swapchain_extent);
+
+    let memory = device.map_memory(
+        data.uniform_buffers_memory[image_index],
+        0,
+        size_of::<UniformBufferObject>() as vk::DeviceSize,
+        vk::MemoryMapFlags::empty(),
+    )?;
+
+    memcpy(&ubo, memory.cast(), 1);
+
+    device.unmap_memory(data.uniform_buffers_memory[image_index]);
+
+    Ok(())
+}
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::vk;
+
+/// The environment variable enabling (`1`) or disabling (`0`) validation.
+pub const VALIDATION_VAR: &str = "VULKAN_VALIDATION";
+/// The environment variable setting the lowest reported severity.
+pub const SEVERITY_VAR: &str = "VULKAN_VALIDATION_SEVERITY";
+/// The environment variable listing the reported message types.
+pub const TYPES_VAR: &str = "VULKAN_VALIDATION_TYPES";
+/// The environment variable listing message IDs to suppress.
+pub const SUPPRESS_VAR: &str = "VULKAN_VALIDATION_SUPPRESS";
+/// The environment variable selecting a `FailFast` mode.
+pub const FAIL_FAST_VAR: &str = "VULKAN_VALIDATION_FAIL_FAST";
+
+/// What to do once the validation layer reported an error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FailFast {
+    /// Return an error from the next frame or teardown.
+    Error,
+    /// Panic in the next frame or teardown.
+    Panic,
+}
+
+/// Whether the validation layer is enabled and which of its messages we
+/// report.
+#[derive(Clone, Debug)]
+pub struct ValidationConfig {
+    pub enabled: bool,
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub types: vk::DebugUtilsMessageTypeFlagsEXT,
+    /// Message ID names (e.g. `VUID-vkCmdDraw-None-02699`) or numbers
+    /// (decimal or `0x` hex) that are never reported.
+    pub suppressed: Vec<String>,
+    /// Fail once an error was reported, e.g. to catch them in tests.
+    pub fail_fast: Option<FailFast>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            severity: vk::DebugUtilsMessageSeverityFlagsEXT::all(),
+            types: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            suppressed: vec![],
+            fail_fast: None,
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// Applies the validation environment variables that are set.
+    pub fn apply_env(&mut self) -> Result<()> {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(value) = var(VALIDATION_VAR) {
+            self.enabled = parse_bool(&value)?;
+        }
+
+        if let Some(value) = var(SEVERITY_VAR) {
+            self.severity = parse_severity(&value)?;
+        }
+
+        if let Some(value) = var(TYPES_VAR) {
+            self.types = parse_types(&value)?;
+        }
+
+        if let Some(value) = var(SUPPRESS_VAR) {
+            self.suppressed.extend(parse_list(&value));
+        }
+
+        if let Some(value) = var(FAIL_FAST_VAR) {
+            self.fail_fast = parse_fail_fast(&value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether a message with the ID `name` and `number` is suppressed.
+    pub fn is_suppressed(&self, name: Option<&str>, number: i32) -> bool {
+        self.suppressed.iter().any(|id| {
+            Some(id.as_str()) == name
+                || id.parse::<i32>().ok() == Some(number)
+                || id
+                    .strip_prefix("0x")
+                    .and_then(|h| u32::from_str_radix(h, 16).ok())
+                    == Some(number as u32)
+        })
+    }
+}
+
+/// How many warnings and errors were reported with a message ID.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageCounts {
+    pub warnings: u32,
+    pub errors: u32,
+}
+
+/// The validation settings together with the messages reported so far,
+/// shared with the debug callback.
+#[derive(Debug, Default)]
+pub struct Validation {
+    pub config: ValidationConfig,
+    counts: Mutex<BTreeMap<String, MessageCounts>>,
+}
+
+impl Validation {
+    pub fn new(config: ValidationConfig) -> Self {
+        Self { config, counts: Mutex::default() }
+    }
+
+    /// Counts a reported message. Messages below warning severity are not
+    /// counted.
+    pub fn record(&self, id: &str, severity: vk::DebugUtilsMessageSeverityFlagsEXT) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        if severity >= vk::DebugUtilsMessageSeverityFlagsEXT::ERROR {
+            counts.entry(id.into()).or_default().errors += 1;
+        } else if severity >= vk::DebugUtilsMessageSeverityFlagsEXT::WARNING {
+            counts.entry(id.into()).or_default().warnings += 1;
+        }
+    }
+
+    /// Returns the warnings and errors reported so far, by message ID.
+    pub fn get_counts(&self) -> BTreeMap<String, MessageCounts> {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn get_error_count(&self) -> u32 {
+        self.get_counts().values().map(|c| c.errors).sum()
+    }
+
+    pub fn get_warning_count(&self) -> u32 {
+        self.get_counts().values().map(|c| c.warnings).sum()
+    }
+
+    /// Fails according to `config.fail_fast` if any error was reported.
+    pub fn check(&self) -> Result<()> {
+        let Some(fail_fast) = self.config.fail_fast else {
+            return Ok(());
+        };
+
+        let errors = self.get_counts()
+            .into_iter()
+            .filter(|(_, c)| c.errors > 0)
+            .map(|(id, c)| format!("{} (x{})", id, c.errors))
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        let message = format!("Validation reported errors: {}.", errors.join(", "));
+        match fail_fast {
+            FailFast::Error => Err(anyhow!(message)),
+            FailFast::Panic => panic!("{}", message),
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Ok(true),
+        "0" | "false" | "off" | "no" => Ok(false),
+        _ => Err(anyhow!("Expected a boolean, got `{}`.", value)),
+    }
+}
+
+fn parse_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from)
+}
+
+/// Parses the lowest severity to report, such as `warning`, into the flags
+/// for it and every higher severity.
+pub fn parse_severity(value: &str) -> Result<vk::DebugUtilsMessageSeverityFlagsEXT> {
+    use vk::DebugUtilsMessageSeverityFlagsEXT as S;
+    match value.to_lowercase().as_str() {
+        "verbose" => Ok(S::VERBOSE | S::INFO | S::WARNING | S::ERROR),
+        "info" => Ok(S::INFO | S::WARNING | S::ERROR),
+        "warning" => Ok(S::WARNING | S::ERROR),
+        "error" => Ok(S::ERROR),
+        _ => Err(anyhow!("Unknown severity `{}`, expected verbose, info, warning or error.", value)),
+    }
+}
+
+/// Parses a comma-separated list of message types such as
+/// `validation,performance`.
+pub fn parse_types(value: &str) -> Result<vk::DebugUtilsMessageTypeFlagsEXT> {
+    use vk::DebugUtilsMessageTypeFlagsEXT as T;
+    parse_list(value).try_fold(T::empty(), |types, t| match t.to_lowercase().as_str() {
+        "general" => Ok(types | T::GENERAL),
+        "validation" => Ok(types | T::VALIDATION),
+        "performance" => Ok(types | T::PERFORMANCE),
+        _ => Err(anyhow!("Unknown message type `{}`, expected general, validation or performance.", t)),
+    })
+}
+
+/// Parses a comma-separated list of message IDs to suppress.
+pub fn parse_suppressed(value: &str) -> Vec<String> {
+    parse_list(value).collect()
+}
+
+/// Parses a `FailFast` mode: `error`, `panic` or `off`.
+pub fn parse_fail_fast(value: &str) -> Result<Option<FailFast>> {
+    match value.to_lowercase().as_str() {
+        "off" => Ok(None),
+        "error" => Ok(Some(FailFast::Error)),
+        "panic" => Ok(Some(FailFast::Panic)),
+        _ => Err(anyhow!("Unknown fail-fast mode `{}`, expected error, panic or off.", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vk::DebugUtilsMessageSeverityFlagsEXT as S;
+
+    fn validation(fail_fast: Option<FailFast>) -> Validation {
+        Validation::new(ValidationConfig { fail_fast, ..Default::default() })
+    }
+
+    #[test]
+    fn test_record() {
+        let validation = validation(None);
+        validation.record("A", S::ERROR);
+        validation.record("A", S::ERROR);
+        validation.record("A", S::WARNING);
+        validation.record("B", S::WARNING);
+        validation.record("C", S::INFO);
+        validation.record("C", S::VERBOSE);
+
+        let counts = validation.get_counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["A"], MessageCounts { warnings: 1, errors: 2 });
+        assert_eq!(counts["B"], MessageCounts { warnings: 1, errors: 0 });
+        assert_eq!(validation.get_error_count(), 2);
+        assert_eq!(validation.get_warning_count(), 2);
+    }
+
+    #[test]
+    fn test_check_passes_without_errors() {
+        for fail_fast in [None, Some(FailFast::Error), Some(FailFast::Panic)] {
+            let validation = validation(fail_fast);
+            assert!(validation.check().is_ok());
+
+            validation.record("A", S::WARNING);
+            assert!(validation.check().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_check_ignores_errors_when_off() {
+        let validation = validation(None);
+        validation.record("A", S::ERROR);
+        assert!(validation.check().is_ok());
+    }
+
+    #[test]
+    fn test_check_fails_on_errors() {
+        let validation = validation(Some(FailFast::Error));
+        validation.record("VUID-A", S::ERROR);
+        validation.record("VUID-A", S::ERROR);
+        validation.record("VUID-B", S::WARNING);
+
+        let message = validation.check().unwrap_err().to_string();
+        assert!(message.contains("VUID-A (x2)"), "{}", message);
+        assert!(!message.contains("VUID-B"), "{}", message);
+    }
+
+    #[test]
+    #[should_panic(expected = "VUID-A (x1)")]
+    fn test_check_panics_on_errors() {
+        let validation = validation(Some(FailFast::Panic));
+        validation.record("VUID-A", S::ERROR);
+        let _ = validation.check();
+    }
+
+    #[test]
+    fn test_is_suppressed() {
+        let config = ValidationConfig {
+            suppressed: parse_suppressed("VUID-vkCmdDraw-None-02699, 42, 0xDEADBEEF"),
+            ..Default::default()
+        };
+
+        assert!(config.is_suppressed(Some("VUID-vkCmdDraw-None-02699"), 1));
+        assert!(config.is_suppressed(None, 42));
+        assert!(config.is_suppressed(Some("Other"), 42));
+        assert!(config.is_suppressed(None, 0xDEADBEEFu32 as i32));
+
+        assert!(!config.is_suppressed(Some("VUID-vkCmdDraw-None"), 1));
+        assert!(!config.is_suppressed(None, 43));
+        assert!(!config.is_suppressed(None, 0x0EADBEEF));
+    }
+
+    #[test]
+    fn test_parse_severity() {
+        assert_eq!(parse_severity("warning").unwrap(), S::WARNING | S::ERROR);
+        assert_eq!(parse_severity("ERROR").unwrap(), S::ERROR);
+        assert!(parse_severity("").is_err());
+        assert!(parse_severity("loud").is_err());
+        assert!(parse_severity("warning,error").is_err());
+    }
+
+    #[test]
+    fn test_parse_types() {
+        use vk::DebugUtilsMessageTypeFlagsEXT as T;
+        assert_eq!(parse_types("validation, performance").unwrap(), T::VALIDATION | T::PERFORMANCE);
+        assert_eq!(parse_types("").unwrap(), T::empty());
+        assert!(parse_types("validation,graphics").is_err());
+        assert!(parse_types("all").is_err());
+    }
+
+    #[test]
+    fn test_parse_fail_fast() {
+        assert_eq!(parse_fail_fast("off").unwrap(), None);
+        assert_eq!(parse_fail_fast("error").unwrap(), Some(FailFast::Error));
+        assert_eq!(parse_fail_fast("Panic").unwrap(), Some(FailFast::Panic));
+        assert!(parse_fail_fast("abort").is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use std::hash::{Hash, Hasher};
+use std::mem::{offset_of, size_of};
+
+use anyhow::{anyhow, Result};
+use cgmath::{Vector2, Vector3};
+use vulkanalia::vk::{self, HasBuilder};
+
+use crate::reflect::ShaderLayout;
+
+pub type Vec2 = Vector2<f32>;
+pub type Vec3 = Vector3<f32>;
+
+/// A vertex as laid out in our vertex buffers and consumed by the vertex shader.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Vertex {
+    pub pos: Vec3,
+    pub color: Vec3,
+    pub normal: Vec3,
+    pub tex_coord: Vec2,
+}
+
+impl Vertex {
+    pub const fn new(pos: Vec3, color: Vec3, normal: Vec3, tex_coord: Vec2) -> Self {
+        Self { pos, color, normal, tex_coord }
+    }
+
+    /// Describes how vertices are read from the buffer bound at binding 0.
+    pub fn binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::builder()
+            .binding(0)
+            .stride(size_of::<Vertex>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .build()
+    }
+
+    /// Describes the vertex shader inputs, one per field, in location order.
+    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
+        let pos = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(Vertex, pos) as u32)
+            .build();
+
+        let color = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(1)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(Vertex, color) as u32)
+            .build();
+
+        let normal = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(2)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(Vertex, normal) as u32)
+            .build();
+
+        let tex_coord = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(3)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(offset_of!(Vertex, tex_coord) as u32)
+            .build();
+
+        [pos, color, normal, tex_coord]
+    }
+
+    /// Checks that the vertex input derived from the shaders in `layout`
+    /// reads our vertex buffers the way vertices are laid out in them.
+    pub fn check_shader_layout(layout: &ShaderLayout) -> Result<()> {
+        let (binding, attributes) = layout.get_vertex_input(0)?;
+        if binding != Self::binding_description() || attributes != Self::attribute_descriptions() {
+            return Err(anyhow!(
+                "The vertex shader reads vertices of {} bytes as {:?}, but `Vertex` is {} bytes laid out as {:?}.",
+                binding.stride,
+                attributes,
+                size_of::<Vertex>(),
+                Self::attribute_descriptions(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The bit patterns of every component, which vertices are compared and
+    /// hashed by.
+    fn to_bits(self) -> [u32; 11] {
+        [
+            self.pos[0].to_bits(),
+            self.pos[1].to_bits(),
+            self.pos[2].to_bits(),
+            self.color[0].to_bits(),
+            self.color[1].to_bits(),
+            self.color[2].to_bits(),
+            self.normal[0].to_bits(),
+            self.normal[1].to_bits(),
+            self.normal[2].to_bits(),
+            self.tex_coord[0].to_bits(),
+            self.tex_coord[1].to_bits(),
+        ]
+    }
+}
+
+// Vertices are compared bitwise so they can be deduplicated through a
+// `HashMap`: `0.0` and `-0.0` differ, and a `NaN` equals itself.
+impl PartialEq for Vertex {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl Eq for Vertex {}
+
+impl Hash for Vertex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state);
+    }
+}